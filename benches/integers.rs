@@ -0,0 +1,106 @@
+//! 94 進数の整数のエンコード・デコードの速度を測る
+//!
+//! Usage: cargo bench --bench integers [-- --full]
+//!
+//! lambdaman の移動列を 1 手 2 ビットで詰めた整数を使う。
+//! 1 桁ずつ変換する素朴な実装は 1MB のエンコードに 20 分ほどかかるので、
+//! `--full` を付けなければ 1MB は `NAIVE_BUDGET` の間だけ変換して全体の時間を見積もる。
+
+use std::time::{Duration, Instant};
+
+use icfpc2024::token::integers;
+use num_bigint::{BigInt, Sign};
+
+/// 1MB の素朴なエンコードに使う時間
+const NAIVE_BUDGET: Duration = Duration::from_secs(30);
+
+/// 以前の実装と同じく 1 桁ずつ 94 で割る
+fn naive_encode(value: BigInt) -> String {
+    naive_encode_until(value, None).into_iter().rev().collect()
+}
+
+/// `deadline` までに求まった下の桁（下から順）
+fn naive_encode_until(mut value: BigInt, deadline: Option<Instant>) -> Vec<char> {
+    let mut digits = vec![];
+    while value > BigInt::ZERO && deadline.is_none_or(|deadline| Instant::now() < deadline) {
+        digits.push((u8::try_from(&value % 94).unwrap() + b'!') as char);
+        value /= 94;
+    }
+    digits
+}
+
+/// 以前の実装と同じく 1 桁ずつ 94 を掛ける
+fn naive_decode(s: &str) -> BigInt {
+    s.bytes().fold(BigInt::ZERO, |acc, b| acc * 94 + (b - b'!'))
+}
+
+/// 線形合同法で作った `moves` 手の移動列を 1 手 2 ビットで詰める
+fn packed_path(moves: usize) -> BigInt {
+    let mut x = 1u64;
+    let path = (0..moves)
+        .map(|_| {
+            x = x * 48271 % 2147483647;
+            b"UDLR"[(x % 4) as usize]
+        })
+        .collect::<Vec<_>>();
+    let bytes = path
+        .chunks(4)
+        .map(|chunk| {
+            chunk.iter().fold(0u8, |acc, c| {
+                acc << 2 | b"UDLR".iter().position(|d| d == c).unwrap() as u8
+            })
+        })
+        .collect::<Vec<_>>();
+    BigInt::from_bytes_be(Sign::Plus, &bytes)
+}
+
+fn measure<T>(f: impl FnOnce() -> T) -> (T, Duration) {
+    let start = Instant::now();
+    let ret = f();
+    (ret, start.elapsed())
+}
+
+fn main() -> anyhow::Result<()> {
+    let full = std::env::args().any(|arg| arg == "--full");
+    for (name, bytes, partial) in [
+        ("10KB", 10_000, false),
+        ("100KB", 100_000, false),
+        ("1MB", 1_000_000, !full),
+    ] {
+        let value = packed_path(bytes * 4);
+        let (encoded, encode_time) = measure(|| integers::encode(value.clone()));
+        let encoded = encoded?;
+        let (decoded, decode_time) = measure(|| integers::decode(encoded.bytes()));
+        assert_eq!(decoded?, value);
+        println!(
+            "{name}: {} digits, encode {encode_time:?}, decode {decode_time:?}",
+            encoded.len()
+        );
+        let (naive_decoded, naive_decode_time) = measure(|| naive_decode(&encoded));
+        assert_eq!(naive_decoded, value);
+        let naive_encode_time = if partial {
+            let deadline = Instant::now() + NAIVE_BUDGET;
+            let (digits, elapsed) = measure(|| naive_encode_until(value.clone(), Some(deadline)));
+            assert!(encoded.chars().rev().zip(&digits).all(|(a, b)| a == *b));
+            // i 桁目を求めるのに残りの桁数に比例する時間がかかるとして全体を見積もる
+            let (n, d) = (encoded.len() as f64, digits.len() as f64);
+            let estimate = elapsed.mul_f64(n * n / (d * (2.0 * n - d)));
+            println!(
+                "{name}: naive encode got {} of {} digits in {elapsed:?}, estimated {estimate:?} in total",
+                digits.len(),
+                encoded.len()
+            );
+            estimate
+        } else {
+            let (naive_encoded, naive_encode_time) = measure(|| naive_encode(value.clone()));
+            assert_eq!(naive_encoded, encoded);
+            naive_encode_time
+        };
+        println!(
+            "{name}: naive encode {naive_encode_time:?} ({:.1}x), naive decode {naive_decode_time:?} ({:.1}x)",
+            naive_encode_time.as_secs_f64() / encode_time.as_secs_f64(),
+            naive_decode_time.as_secs_f64() / decode_time.as_secs_f64(),
+        );
+    }
+    Ok(())
+}
@@ -1,22 +1,551 @@
+//! ICFP のトークン列を書くためのアセンブラ
+//!
+//! - `123` は整数、`"Hello World!"` は文字列になる（`\` はそのまま。後ろに空白か行末が続く `"` で終わる）
+//! - `e"say \"hi\"\n"` は `\"` `\\` `\n` をエスケープとして読む文字列になる
+//! - `B$` `U-` `?` `T` `I/6` `S'%4` などのトークンはそのまま出力する
+//! - `Lx` `vx` の変数名は任意の文字列で書け、スコープ内で重ならない最も短い名前に付け直す
+//!   (`Lx@3` のように書くと番号を 3 に固定する)
+//! - `;` から行末まではコメント
+//! - `.macro name a b` から `.endm` までで引数付きのマクロを定義する（本体は 1 つの式）
+//! - `.include "path"` で他のファイルを読み込む（パスは読み込む側のファイルからの相対パス）
+//! - `Y` `repeat` `fold_digits` `reverse` `char_at` は組み込みのマクロ (`assemble/prelude.asm`)
+//!
+//! 各トークンの引数の数から式の範囲を決めるので、マクロの引数にも任意の式を書ける。
+//! 引数が足りない・式が 1 つにならないときは位置付きのエラーにし、
+//! 束縛されていない変数や変数の隠蔽は警告する。
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt, fs,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use anyhow::{bail, Context};
+use num_bigint::BigInt;
+
 use crate::token::{integers, strings};
-use anyhow::Context;
 
-fn parse_mnemonic(mn: &str) -> anyhow::Result<String> {
-    if mn.chars().all(|c| c.is_ascii() && c.is_numeric()) {
-        Ok(format!("I{}", integers::encode(mn.parse()?)?))
-    } else if mn.starts_with('"') {
-        let (_, mn) = mn.split_once('"').expect("unreachable");
-        let (mn, _) = mn
-            .rsplit_once('"')
-            .context("String literal does not end with '\"'")?;
-        Ok(format!("S{}", strings::encode(mn)?))
-    } else if mn == "Y" {
-        Ok(r#"L" B$ L# B$ v" B$ v# v# L# B$ v" B$ v# v#"#.to_owned())
-    } else {
-        Ok(mn.to_owned())
+/// 組み込みのマクロ
+const PRELUDE: &str = include_str!("assemble/prelude.asm");
+
+/// ファイル名（標準入力なら空）と 1 始まりの行・列
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Location {
+    file: Rc<str>,
+    line: usize,
+    col: usize,
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.file.is_empty() {
+            write!(f, "{}:", self.file)?;
+        }
+        write!(f, "{}:{}", self.line, self.col)
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Word {
+    text: String,
+    loc: Location,
+}
+
+/// 空白で区切ってコメントを取り除く（文字列は空白を含んでもよい）
+fn lex(src: &str, file: &Rc<str>) -> anyhow::Result<Vec<Word>> {
+    let mut words = vec![];
+    for (i, line) in src.lines().enumerate() {
+        let mut chars = line.chars().enumerate().peekable();
+        while let Some(&(j, c)) = chars.peek() {
+            if c.is_whitespace() {
+                chars.next();
+                continue;
+            }
+            if c == ';' {
+                break;
+            }
+            let loc = Location {
+                file: file.clone(),
+                line: i + 1,
+                col: j + 1,
+            };
+            let mut text = String::new();
+            let escaped = c == 'e' && chars.clone().nth(1).is_some_and(|(_, c)| c == '"');
+            if escaped {
+                text.push(c);
+                chars.next();
+            }
+            if escaped || c == '"' {
+                text.push('"');
+                chars.next();
+                loop {
+                    let Some((_, c)) = chars.next() else {
+                        bail!("Unterminated string at {loc}");
+                    };
+                    text.push(c);
+                    match c {
+                        '"' if escaped => break,
+                        '"' if chars.peek().is_none_or(|(_, c)| c.is_whitespace()) => break,
+                        '\\' if escaped => text.extend(chars.next().map(|(_, c)| c)),
+                        _ => {}
+                    }
+                }
+            } else {
+                while let Some(&(_, c)) = chars.peek() {
+                    if c.is_whitespace() {
+                        break;
+                    }
+                    text.push(c);
+                    chars.next();
+                }
+            }
+            words.push(Word { text, loc });
+        }
+    }
+    Ok(words)
+}
+
+/// 文字列の中身（`e"..."` ならエスケープを戻す）
+fn unquote(literal: &str) -> anyhow::Result<String> {
+    let (escaped, literal) = match literal.strip_prefix('e') {
+        Some(literal) => (true, literal),
+        None => (false, literal),
+    };
+    let inner = literal
+        .strip_prefix('"')
+        .and_then(|s| s.strip_suffix('"'))
+        .context("String literal does not end with '\"'")?;
+    if !escaped {
+        return Ok(inner.to_owned());
+    }
+    let mut s = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            s.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => s.push('\n'),
+            Some(c @ ('"' | '\\')) => s.push(c),
+            c => bail!(
+                "Unknown escape: \\{}",
+                c.map(String::from).unwrap_or_default()
+            ),
+        }
+    }
+    Ok(s)
+}
+
+/// 変数名と、それを束縛したマクロの展開ごとの番号（0 はマクロの外）
+type Symbol = (String, usize);
+
+#[derive(Clone, Debug)]
+enum Node {
+    /// エンコード済みの引数を取らないトークン
+    Leaf(String),
+    /// `U` `B` `?` と引数
+    Op(String, Vec<Node>),
+    /// 束縛する変数と固定された番号
+    Lambda(Symbol, Option<BigInt>, Location, Box<Node>),
+    Var(Symbol, Location),
+}
+
+#[derive(Clone, Debug)]
+struct Macro {
+    params: Vec<String>,
+    body: Vec<Word>,
+}
+
+/// マクロの展開中に名前を解決するための環境
+struct Scope<'a> {
+    args: HashMap<String, Node>,
+    /// マクロの本体で束縛される変数名（展開ごとに別の変数になる）
+    bound: HashSet<String>,
+    tag: usize,
+    outer: Option<&'a Scope<'a>>,
+}
+
+impl Scope<'_> {
+    fn symbol(&self, name: &str) -> Symbol {
+        match self.outer {
+            Some(outer) if !self.bound.contains(name) => outer.symbol(name),
+            _ => (name.to_owned(), self.tag),
+        }
+    }
+}
+
+struct Cursor<'a> {
+    words: &'a [Word],
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn peek(&self) -> Option<&'a Word> {
+        self.words.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<&'a Word> {
+        let word = self.words.get(self.pos)?;
+        self.pos += 1;
+        Some(word)
+    }
+
+    /// `word` と同じ行の残りの語
+    fn rest_of_line(&mut self, word: &Word) -> Vec<&'a Word> {
+        let mut words = vec![];
+        while let Some(next) = self.peek().filter(|next| next.loc.line == word.loc.line) {
+            words.push(next);
+            self.pos += 1;
+        }
+        words
+    }
+}
+
+#[derive(Default)]
+pub struct Assembler {
+    macros: HashMap<String, Macro>,
+    /// マクロを展開した回数
+    expansions: usize,
+    /// 読み込み中のファイル（循環した include を検出する）
+    including: Vec<PathBuf>,
+    warnings: Vec<String>,
+}
+
+impl Assembler {
+    pub fn new() -> Self {
+        let mut assembler = Assembler::default();
+        assembler
+            .source(PRELUDE, "prelude".into(), Path::new("."))
+            .expect("Invalid prelude");
+        assembler
+    }
+
+    /// 直前のアセンブルで出た警告
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+
+    /// ファイルを読み込んでアセンブルする
+    pub fn assemble_file(&mut self, path: &Path) -> anyhow::Result<Vec<String>> {
+        let nodes = self.include(path)?;
+        self.emit(nodes)
+    }
+
+    /// `dir` を include の基準にしてアセンブルする
+    pub fn assemble(&mut self, src: &str, dir: &Path) -> anyhow::Result<Vec<String>> {
+        let nodes = self.source(src, "".into(), dir)?;
+        self.emit(nodes)
+    }
+
+    /// 全体が 1 つの式になっていることを確かめて出力する
+    fn emit(&mut self, nodes: Vec<(Location, Node)>) -> anyhow::Result<Vec<String>> {
+        let mut nodes = nodes.into_iter();
+        let (_, node) = nodes.next().context("Empty program")?;
+        if let Some((loc, _)) = nodes.next() {
+            bail!("Trailing tokens at {loc}: the program must be a single expression");
+        }
+        let mut emitter = Emitter::default();
+        emitter.free_numbers(&node, &mut vec![])?;
+        emitter.emit(&node)?;
+        self.warnings = emitter.warnings;
+        Ok(emitter.tokens)
+    }
+
+    fn include(&mut self, path: &Path) -> anyhow::Result<Vec<(Location, Node)>> {
+        let canonical = path
+            .canonicalize()
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        if self.including.contains(&canonical) {
+            bail!("Circular include: {}", path.display());
+        }
+        let src = fs::read_to_string(path)?;
+        self.including.push(canonical);
+        let dir = path.parent().unwrap_or(Path::new("."));
+        let nodes = self.source(&src, path.to_string_lossy().into(), dir);
+        self.including.pop();
+        nodes
+    }
+
+    /// マクロの定義や include を処理しながら式を並べる
+    fn source(
+        &mut self,
+        src: &str,
+        file: Rc<str>,
+        dir: &Path,
+    ) -> anyhow::Result<Vec<(Location, Node)>> {
+        let words = lex(src, &file)?;
+        let mut cursor = Cursor {
+            words: &words,
+            pos: 0,
+        };
+        let scope = Scope {
+            args: HashMap::new(),
+            bound: HashSet::new(),
+            tag: 0,
+            outer: None,
+        };
+        let mut nodes = vec![];
+        while let Some(word) = cursor.peek() {
+            match word.text.as_str() {
+                ".macro" => {
+                    cursor.next();
+                    self.define(word, &mut cursor)?;
+                }
+                ".include" => {
+                    cursor.next();
+                    let args = cursor.rest_of_line(word);
+                    let [path] = args[..] else {
+                        bail!("Expected one path at {}", word.loc);
+                    };
+                    let path = dir.join(unquote(&path.text)?);
+                    nodes.extend(self.include(&path)?);
+                }
+                _ => nodes.push((word.loc.clone(), self.expr(&mut cursor, &scope)?)),
+            }
+        }
+        Ok(nodes)
+    }
+
+    /// `.macro` の後を `.endm` まで読む
+    fn define(&mut self, word: &Word, cursor: &mut Cursor) -> anyhow::Result<()> {
+        let header = cursor.rest_of_line(word);
+        let Some((name, params)) = header.split_first() else {
+            bail!("Expected macro name at {}", word.loc);
+        };
+        let mut body = vec![];
+        loop {
+            let Some(word) = cursor.next() else {
+                bail!("Macro {} at {} is not closed by .endm", name.text, name.loc);
+            };
+            match word.text.as_str() {
+                ".endm" => break,
+                ".macro" | ".include" => {
+                    bail!("Unexpected {} in macro at {}", word.text, word.loc)
+                }
+                _ => body.push(word.clone()),
+            }
+        }
+        self.macros.insert(
+            name.text.clone(),
+            Macro {
+                params: params.iter().map(|p| p.text.clone()).collect(),
+                body,
+            },
+        );
+        Ok(())
+    }
+
+    /// `word` の `i` 番目 (0 始まり) の引数を読む
+    fn operand(
+        &mut self,
+        cursor: &mut Cursor,
+        scope: &Scope,
+        word: &Word,
+        i: usize,
+        arity: usize,
+    ) -> anyhow::Result<Node> {
+        if cursor.peek().is_none() {
+            bail!(
+                "{} at {} is missing operands: expected {arity}, got {i}",
+                word.text,
+                word.loc
+            );
+        }
+        self.expr(cursor, scope)
+    }
+
+    /// 1 つの式を読んでマクロを展開する
+    fn expr(&mut self, cursor: &mut Cursor, scope: &Scope) -> anyhow::Result<Node> {
+        let word = cursor.next().context("Unexpected end of input")?;
+        let text = word.text.as_str();
+        if matches!(text, ".macro" | ".endm" | ".include") {
+            bail!("Unexpected {text} at {} inside an expression", word.loc);
+        }
+        if let Some(arg) = scope.args.get(text) {
+            return Ok(arg.clone());
+        }
+        if text.starts_with('"') || text.starts_with("e\"") {
+            return Ok(Node::Leaf(format!(
+                "S{}",
+                strings::encode(&unquote(text)?)?
+            )));
+        }
+        if text.chars().all(|c| c.is_ascii_digit()) {
+            return Ok(Node::Leaf(format!("I{}", integers::encode(text.parse()?)?)));
+        }
+        if let Some(m) = self.macros.get(text).cloned() {
+            let arity = m.params.len();
+            let args = (0..arity)
+                .map(|i| self.operand(cursor, scope, word, i, arity))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            self.expansions += 1;
+            let bound = m
+                .body
+                .iter()
+                .filter_map(|w| w.text.strip_prefix('L'))
+                .map(|binder| parse_binder(binder).0.to_owned())
+                .collect();
+            let inner = Scope {
+                args: m.params.into_iter().zip(args).collect(),
+                bound,
+                tag: self.expansions,
+                outer: Some(scope),
+            };
+            let mut body = Cursor {
+                words: &m.body,
+                pos: 0,
+            };
+            let node = self
+                .expr(&mut body, &inner)
+                .with_context(|| format!("In macro {text} expanded at {}", word.loc))?;
+            if let Some(rest) = body.peek() {
+                bail!(
+                    "Macro {text} must be a single expression: trailing tokens at {}",
+                    rest.loc
+                );
+            }
+            return Ok(node);
+        }
+        let arity = match text.chars().next() {
+            Some('T' | 'F' | 'I' | 'S') => 0,
+            Some('U') => 1,
+            Some('B') => 2,
+            Some('?') => 3,
+            Some('L') if text.len() > 1 => {
+                let (name, number) = parse_binder(&text[1..]);
+                let var = scope.symbol(name);
+                let body = self.operand(cursor, scope, word, 0, 1)?;
+                return Ok(Node::Lambda(var, number, word.loc.clone(), Box::new(body)));
+            }
+            Some('v') if text.len() > 1 => {
+                return Ok(Node::Var(scope.symbol(&text[1..]), word.loc.clone()))
+            }
+            _ => bail!("Unknown mnemonic at {}: {text}", word.loc),
+        };
+        let args = (0..arity)
+            .map(|i| self.operand(cursor, scope, word, i, arity))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        Ok(if args.is_empty() {
+            Node::Leaf(text.to_owned())
+        } else {
+            Node::Op(text.to_owned(), args)
+        })
     }
 }
 
+/// 変数に番号を付けてトークン列にする
+#[derive(Default)]
+struct Emitter {
+    /// 束縛されていない変数の番号
+    reserved: HashSet<BigInt>,
+    /// 外側から順に束縛されている変数
+    scope: Vec<(Symbol, Location, BigInt)>,
+    tokens: Vec<String>,
+    warnings: Vec<String>,
+}
+
+impl Emitter {
+    /// 束縛されていない変数は名前を 94 進数として読んでそのまま使う
+    fn free_numbers(&mut self, node: &Node, bound: &mut Vec<Symbol>) -> anyhow::Result<()> {
+        match node {
+            Node::Leaf(_) => {}
+            Node::Op(_, args) => {
+                for arg in args {
+                    self.free_numbers(arg, bound)?;
+                }
+            }
+            Node::Lambda(var, _, _, body) => {
+                bound.push(var.clone());
+                self.free_numbers(body, bound)?;
+                bound.pop();
+            }
+            Node::Var(var, loc) => {
+                if !bound.contains(var) {
+                    let number = integers::decode(var.0.bytes()).with_context(|| {
+                        format!("Unbound variable v{} at {loc} is not a number", var.0)
+                    })?;
+                    self.warnings
+                        .push(format!("Unbound variable v{} at {loc}", var.0));
+                    self.reserved.insert(number);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn emit(&mut self, node: &Node) -> anyhow::Result<()> {
+        match node {
+            Node::Leaf(token) => self.tokens.push(token.clone()),
+            Node::Op(op, args) => {
+                self.tokens.push(op.clone());
+                for arg in args {
+                    self.emit(arg)?;
+                }
+            }
+            Node::Lambda(var, fixed, loc, body) => {
+                if let Some((_, outer, _)) = self.scope.iter().rev().find(|(v, _, _)| v == var) {
+                    self.warnings
+                        .push(format!("L{} at {loc} shadows L{} at {outer}", var.0, var.0));
+                }
+                // 外側の変数と重ならない最小の番号
+                let number = fixed.clone().unwrap_or_else(|| {
+                    (1..)
+                        .map(BigInt::from)
+                        .find(|n| {
+                            !self.reserved.contains(n) && self.scope.iter().all(|(_, _, m)| m != n)
+                        })
+                        .expect("unreachable")
+                });
+                self.tokens.push(format!("L{}", encode_number(&number)));
+                self.scope.push((var.clone(), loc.clone(), number));
+                let ret = self.emit(body);
+                self.scope.pop();
+                ret?;
+            }
+            Node::Var(var, loc) => {
+                let found = self.scope.iter().rposition(|(v, _, _)| v == var);
+                let (number, token) = match found {
+                    Some(i) => (self.scope[i].2.clone(), encode_number(&self.scope[i].2)),
+                    None => (integers::decode(var.0.bytes())?, var.0.clone()),
+                };
+                // 番号を固定した内側の変数に取られていないか
+                let start = found.map_or(0, |i| i + 1);
+                if let Some((inner, inner_loc, _)) =
+                    self.scope[start..].iter().find(|(_, _, n)| *n == number)
+                {
+                    bail!(
+                        "v{} at {loc} is captured by L{} at {inner_loc}",
+                        var.0,
+                        inner.0
+                    );
+                }
+                self.tokens.push(format!("v{token}"));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `x@3` を名前と固定された番号に分ける
+fn parse_binder(binder: &str) -> (&str, Option<BigInt>) {
+    binder
+        .rsplit_once('@')
+        .filter(|(name, number)| {
+            !name.is_empty() && !number.is_empty() && number.bytes().all(|b| b.is_ascii_digit())
+        })
+        .map_or((binder, None), |(name, number)| (name, number.parse().ok()))
+}
+
+fn encode_number(number: &BigInt) -> String {
+    integers::encode(number.clone()).expect("Variable numbers are non-negative")
+}
+
 pub fn assemble(asm: &str) -> anyhow::Result<Vec<String>> {
-    asm.split_ascii_whitespace().map(parse_mnemonic).collect()
+    Assembler::new().assemble(asm, Path::new("."))
+}
+
+pub fn assemble_file(path: &Path) -> anyhow::Result<Vec<String>> {
+    Assembler::new().assemble_file(path)
 }
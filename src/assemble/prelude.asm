@@ -0,0 +1,30 @@
+; 組み込みのマクロ
+;
+; どれも引数を取らない閉じた式なので `B$` で適用して使う。
+; `src/ast/stdlib.rs` に同じものを `Expr` として組み立てる関数がある。
+
+; Y コンビネータ: B$ Y Lf ...
+.macro Y
+Lf B$ Lx B$ vf B$ vx vx Lx B$ vf B$ vx vx
+.endm
+
+; 文字列 s を n 回繰り返す: B$ B$ repeat s n
+.macro repeat
+B$ Y Lr Ls Ln ? B= vn 0 "" B. vs B$ B$ vr vs B- vn 1
+.endm
+
+; 非負整数 n の k 進数の桁を下の桁から f で畳み込む: B$ B$ B$ B$ fold_digits k f acc n
+; f は B$ B$ f acc digit で次の acc を返す。n = 0 なら acc のまま。
+.macro fold_digits
+Lk Lf B$ Y Lr Lacc Ln ? B= vn 0 vacc B$ B$ vr B$ B$ vf vacc B% vn vk B/ vn vk
+.endm
+
+; 文字列を逆順にする: B$ reverse s
+.macro reverse
+B$ Y Lr Ls ? B= vs "" "" B. B$ vr BD 1 vs BT 1 vs
+.endm
+
+; s の i 文字目 (0 始まり): B$ B$ char_at s i
+.macro char_at
+Ls Li BT 1 BD vi vs
+.endm
@@ -5,6 +5,10 @@ use num_bigint::{BigInt, ToBigInt};
 
 use crate::token::{decode_token_stream, BinaryOp, Token, UnaryOp};
 
+pub mod builder;
+pub mod sexp;
+pub mod stdlib;
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum ThunkEnum {
     Expr(Expr),
@@ -40,11 +44,22 @@ impl Thunk {
     }
 
     pub fn subst(&self, var: BigInt, target: Thunk) -> Self {
+        self.subst_env(&vec![(var, target)])
+    }
+
+    fn subst_env(&self, env: &Env) -> Self {
         match &*self.0.borrow() {
-            ThunkEnum::Expr(e) => e.subst(var, target),
+            ThunkEnum::Expr(e) => e.subst_env(env),
             ThunkEnum::Value(v) => v.clone().into(),
         }
     }
+
+    fn push_tokens(&self, tokens: &mut Vec<Token>) {
+        match &*self.0.borrow() {
+            ThunkEnum::Expr(e) => e.push_tokens(tokens),
+            ThunkEnum::Value(v) => v.push_tokens(tokens),
+        }
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -160,6 +175,39 @@ impl Expr {
         Ok(expr)
     }
 
+    /// 前置記法のトークン列に戻す
+    pub fn to_tokens(&self) -> Vec<Token> {
+        let mut tokens = vec![];
+        self.push_tokens(&mut tokens);
+        tokens
+    }
+
+    fn push_tokens(&self, tokens: &mut Vec<Token>) {
+        match self {
+            Expr::Literal(v) => v.push_tokens(tokens),
+            Expr::UnaryOp(o, e) => {
+                tokens.push(Token::UnaryOp(o.clone()));
+                e.push_tokens(tokens);
+            }
+            Expr::BinaryOp(o, l, r) => {
+                tokens.push(Token::BinaryOp(o.clone()));
+                l.push_tokens(tokens);
+                r.push_tokens(tokens);
+            }
+            Expr::If(c, t, e) => {
+                tokens.push(Token::If);
+                c.push_tokens(tokens);
+                t.push_tokens(tokens);
+                e.push_tokens(tokens);
+            }
+            Expr::Lambda(var, body) => {
+                tokens.push(Token::Lambda(var.clone()));
+                body.push_tokens(tokens);
+            }
+            Expr::Var(var) => tokens.push(Token::Variable(var.clone())),
+        }
+    }
+
     pub fn eval(&self) -> anyhow::Result<Value> {
         match self {
             Expr::Literal(v) => Ok(v.clone()),
@@ -176,37 +224,30 @@ impl Expr {
     }
 
     pub fn subst(&self, var: BigInt, target: Thunk) -> Thunk {
+        self.subst_env(&vec![(var, target)])
+    }
+
+    /// env に含まれる変数をまとめて置換する（後ろにあるものほど内側の束縛）
+    fn subst_env(&self, env: &Env) -> Thunk {
         match self {
             Expr::Literal(_) => self.clone().into(),
-            Expr::UnaryOp(o, e) => Expr::UnaryOp(o.clone(), e.subst(var, target)).into(),
-            Expr::BinaryOp(o, l, r) => Expr::BinaryOp(
-                o.clone(),
-                l.subst(var.clone(), target.clone()),
-                r.subst(var, target),
-            )
-            .into(),
-            Expr::If(c, t, e) => Expr::If(
-                c.subst(var.clone(), target.clone()),
-                t.subst(var.clone(), target.clone()),
-                e.subst(var, target),
-            )
-            .into(),
+            Expr::UnaryOp(o, e) => Expr::UnaryOp(o.clone(), e.subst_env(env)).into(),
+            Expr::BinaryOp(o, l, r) => {
+                Expr::BinaryOp(o.clone(), l.subst_env(env), r.subst_env(env)).into()
+            }
+            Expr::If(c, t, e) => {
+                Expr::If(c.subst_env(env), t.subst_env(env), e.subst_env(env)).into()
+            }
             Expr::Lambda(v, ref body) => {
                 let y = fresh();
-                Expr::Lambda(
-                    y.clone(),
-                    body.subst(v.clone(), Expr::Var(y).into())
-                        .subst(var, target),
-                )
-                .into()
-            }
-            Expr::Var(v) => {
-                if *v == var {
-                    target
-                } else {
-                    Expr::Var(v.clone()).into()
-                }
+                let mut env = env.clone();
+                env.push((v.clone(), Expr::Var(y.clone()).into()));
+                Expr::Lambda(y, body.subst_env(&env)).into()
             }
+            Expr::Var(v) => match env.iter().rev().find(|(var, _)| var == v) {
+                Some((_, target)) => target.clone(),
+                None => Expr::Var(v.clone()).into(),
+            },
         }
     }
 }
@@ -250,13 +291,39 @@ impl From<String> for Value {
     }
 }
 
+impl Value {
+    /// 負の整数は `U-` で表す
+    fn push_tokens(&self, tokens: &mut Vec<Token>) {
+        match self {
+            &Value::Boolean(b) => tokens.push(Token::Boolean(b)),
+            Value::Integer(i) if i.sign() == num_bigint::Sign::Minus => {
+                tokens.push(Token::UnaryOp(UnaryOp::Neg));
+                tokens.push(Token::Integer(-i));
+            }
+            Value::Integer(i) => tokens.push(Token::Integer(i.clone())),
+            Value::String(s) => tokens.push(Token::String(s.clone())),
+            Value::Closure(var, body) => {
+                tokens.push(Token::Lambda(var.clone()));
+                body.push_tokens(tokens);
+            }
+        }
+    }
+}
+
 impl std::fmt::Display for Value {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         use Value::*;
         match self {
             Boolean(b) => b.fmt(f),
             Integer(i) => i.fmt(f),
-            String(s) => write!(f, r#""{s}""#),
+            // S 式として読み戻せるようにエスケープする
+            String(s) => write!(
+                f,
+                r#""{}""#,
+                s.replace('\\', r"\\")
+                    .replace('"', r#"\""#)
+                    .replace('\n', r"\n")
+            ),
             Closure(var, body) => write!(f, "(λ v{var} . {body})"),
         }
     }
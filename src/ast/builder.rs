@@ -0,0 +1,136 @@
+//! `Expr` を組み立てる DSL
+//!
+//! ```
+//! use icfpc2024::ast::builder::*;
+//!
+//! // Y (λf. λn. if n == 0 then "" else "R" . f (n - 1)) 3
+//! let expr = app(
+//!     fix(|f| lam(|n| ite(eq(n.clone(), int(0)), string(""), concat(string("R"), app(f, sub(n, int(1))))))),
+//!     int(3),
+//! );
+//! assert_eq!(expr.eval().unwrap(), "RRR".to_owned().into());
+//! ```
+//!
+//! `lam` は束縛の深さから変数の番号を決めるので、番号はスコープ内で重ならない最も短いものになる。
+//! `lam` に渡したクロージャの外で作った式は、その中の変数を参照できない。
+
+use std::cell::Cell;
+
+use num_bigint::BigInt;
+
+use super::{stdlib, Expr};
+use crate::token::{BinaryOp, UnaryOp};
+
+thread_local! {
+    /// 組み立て中の `lam` の深さ
+    static DEPTH: Cell<u32> = const { Cell::new(0) };
+}
+
+/// 途中で panic しても深さを戻す
+struct Scope(u32);
+
+impl Scope {
+    fn enter() -> Self {
+        let depth = DEPTH.with(|d| d.replace(d.get() + 1));
+        Scope(depth + 1)
+    }
+}
+
+impl Drop for Scope {
+    fn drop(&mut self) {
+        DEPTH.with(|d| d.set(self.0 - 1));
+    }
+}
+
+/// `λx. f(x)`
+pub fn lam(f: impl FnOnce(Expr) -> Expr) -> Expr {
+    let scope = Scope::enter();
+    let v = BigInt::from(scope.0);
+    let body = f(Expr::Var(v.clone()));
+    Expr::Lambda(v, body.into())
+}
+
+/// `λx. λy. f(x, y)`
+pub fn lam2(f: impl FnOnce(Expr, Expr) -> Expr) -> Expr {
+    lam(|x| lam(|y| f(x, y)))
+}
+
+/// `λx. λy. λz. f(x, y, z)`
+pub fn lam3(f: impl FnOnce(Expr, Expr, Expr) -> Expr) -> Expr {
+    lam(|x| lam(|y| lam(|z| f(x, y, z))))
+}
+
+/// 再帰関数 `Y (λself. f(self))`
+pub fn fix(f: impl FnOnce(Expr) -> Expr) -> Expr {
+    app(stdlib::y(), lam(f))
+}
+
+/// `let x = value in f(x)`
+pub fn let_in(value: Expr, f: impl FnOnce(Expr) -> Expr) -> Expr {
+    app(lam(f), value)
+}
+
+pub fn int(i: impl Into<BigInt>) -> Expr {
+    i.into().into()
+}
+
+pub fn string(s: impl Into<String>) -> Expr {
+    s.into().into()
+}
+
+pub fn unary(o: UnaryOp, e: Expr) -> Expr {
+    Expr::UnaryOp(o, e.into())
+}
+
+pub fn binary(o: BinaryOp, lhs: Expr, rhs: Expr) -> Expr {
+    Expr::BinaryOp(o, lhs.into(), rhs.into())
+}
+
+pub fn app(f: Expr, x: Expr) -> Expr {
+    binary(BinaryOp::Apply, f, x)
+}
+
+/// `f x1 x2 ...`
+pub fn call(f: Expr, args: impl IntoIterator<Item = Expr>) -> Expr {
+    args.into_iter().fold(f, app)
+}
+
+pub fn ite(cond: Expr, then: Expr, otherwise: Expr) -> Expr {
+    Expr::If(cond.into(), then.into(), otherwise.into())
+}
+
+pub fn concat(lhs: Expr, rhs: Expr) -> Expr {
+    binary(BinaryOp::Concat, lhs, rhs)
+}
+
+pub fn eq(lhs: Expr, rhs: Expr) -> Expr {
+    binary(BinaryOp::Equal, lhs, rhs)
+}
+
+pub fn add(lhs: Expr, rhs: Expr) -> Expr {
+    binary(BinaryOp::Add, lhs, rhs)
+}
+
+pub fn sub(lhs: Expr, rhs: Expr) -> Expr {
+    binary(BinaryOp::Sub, lhs, rhs)
+}
+
+pub fn mul(lhs: Expr, rhs: Expr) -> Expr {
+    binary(BinaryOp::Mul, lhs, rhs)
+}
+
+pub fn div(lhs: Expr, rhs: Expr) -> Expr {
+    binary(BinaryOp::Div, lhs, rhs)
+}
+
+pub fn rem(lhs: Expr, rhs: Expr) -> Expr {
+    binary(BinaryOp::Mod, lhs, rhs)
+}
+
+pub fn take(n: Expr, s: Expr) -> Expr {
+    binary(BinaryOp::Take, n, s)
+}
+
+pub fn drop(n: Expr, s: Expr) -> Expr {
+    binary(BinaryOp::Drop, n, s)
+}
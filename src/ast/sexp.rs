@@ -0,0 +1,248 @@
+//! `Expr` の `Display` が出力する S 式を読み込む
+//!
+//! `(λ v1 . (+ v1 v1))` `(if (= v1 0) "zero" "nonzero")` `((λ v1 . v1) 42)` のように書く。
+//! 関数適用は `(f x)` の他に `(Apply f x)` とも書ける。
+
+use anyhow::{bail, Context};
+use num_bigint::BigInt;
+
+use super::Expr;
+use crate::token::{BinaryOp, UnaryOp};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Lexeme {
+    Open,
+    Close,
+    String(String),
+    Atom(String),
+}
+
+/// 字句とその開始位置（バイト単位）に分ける
+fn lex(s: &str) -> anyhow::Result<Vec<(usize, Lexeme)>> {
+    let mut lexemes = vec![];
+    let mut chars = s.char_indices().peekable();
+    while let Some(&(pos, c)) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' => {
+                chars.next();
+                lexemes.push((pos, Lexeme::Open));
+            }
+            ')' => {
+                chars.next();
+                lexemes.push((pos, Lexeme::Close));
+            }
+            '"' => {
+                chars.next();
+                let mut literal = String::new();
+                loop {
+                    match chars.next().context("Unterminated string")? {
+                        (_, '"') => break,
+                        (_, '\\') => match chars.next() {
+                            Some((_, 'n')) => literal.push('\n'),
+                            Some((_, c @ ('"' | '\\'))) => literal.push(c),
+                            Some((i, c)) => bail!("Unknown escape \\{c} at {i}"),
+                            None => bail!("Unterminated string at {pos}"),
+                        },
+                        (_, c) => literal.push(c),
+                    }
+                }
+                lexemes.push((pos, Lexeme::String(literal)));
+            }
+            _ => {
+                let mut atom = String::new();
+                while let Some(&(_, c)) = chars.peek() {
+                    if c.is_whitespace() || matches!(c, '(' | ')' | '"') {
+                        break;
+                    }
+                    atom.push(c);
+                    chars.next();
+                }
+                lexemes.push((pos, Lexeme::Atom(atom)));
+            }
+        }
+    }
+    Ok(lexemes)
+}
+
+fn unary_op(name: &str) -> Option<UnaryOp> {
+    Some(match name {
+        "-" => UnaryOp::Neg,
+        "!" => UnaryOp::Not,
+        "ToInt" => UnaryOp::ToInt,
+        "ToString" => UnaryOp::ToString,
+        _ => return None,
+    })
+}
+
+fn binary_op(name: &str) -> Option<BinaryOp> {
+    Some(match name {
+        "+" => BinaryOp::Add,
+        "-" => BinaryOp::Sub,
+        "*" => BinaryOp::Mul,
+        "/" => BinaryOp::Div,
+        "%" => BinaryOp::Mod,
+        "<" => BinaryOp::Less,
+        ">" => BinaryOp::Greater,
+        "=" => BinaryOp::Equal,
+        "|" => BinaryOp::Or,
+        "&" => BinaryOp::And,
+        "." => BinaryOp::Concat,
+        "Take" => BinaryOp::Take,
+        "Drop" => BinaryOp::Drop,
+        "Apply" => BinaryOp::Apply,
+        _ => return None,
+    })
+}
+
+/// `v1` や置換で作られた `v-1` のような変数名
+fn variable(atom: &str) -> Option<BigInt> {
+    atom.strip_prefix('v')?.parse().ok()
+}
+
+struct Reader {
+    lexemes: Vec<(usize, Lexeme)>,
+    pos: usize,
+    len: usize,
+}
+
+impl Reader {
+    fn peek(&self) -> Option<&Lexeme> {
+        self.lexemes.get(self.pos).map(|(_, l)| l)
+    }
+
+    /// 次の字句の位置（末尾なら入力の長さ）
+    fn offset(&self) -> usize {
+        self.lexemes.get(self.pos).map_or(self.len, |&(i, _)| i)
+    }
+
+    fn next(&mut self) -> anyhow::Result<Lexeme> {
+        let (_, lexeme) = self
+            .lexemes
+            .get(self.pos)
+            .context("Unexpected end of input")?;
+        self.pos += 1;
+        Ok(lexeme.clone())
+    }
+
+    fn atom(&mut self) -> anyhow::Result<String> {
+        let offset = self.offset();
+        match self.next()? {
+            Lexeme::Atom(atom) => Ok(atom),
+            lexeme => bail!("Expected atom at {offset}: got {lexeme:?}"),
+        }
+    }
+
+    fn close(&mut self) -> anyhow::Result<()> {
+        let offset = self.offset();
+        match self.next()? {
+            Lexeme::Close => Ok(()),
+            lexeme => bail!("Expected ')' at {offset}: got {lexeme:?}"),
+        }
+    }
+
+    /// `)` までの式を読む
+    fn args(&mut self) -> anyhow::Result<Vec<Expr>> {
+        let mut args = vec![];
+        while self.peek().is_some_and(|l| *l != Lexeme::Close) {
+            args.push(self.expr()?);
+        }
+        self.close()?;
+        Ok(args)
+    }
+
+    fn expr(&mut self) -> anyhow::Result<Expr> {
+        let offset = self.offset();
+        match self.next()? {
+            Lexeme::String(s) => Ok(s.into()),
+            Lexeme::Atom(atom) => match atom.as_str() {
+                "true" => Ok(true.into()),
+                "false" => Ok(false.into()),
+                _ => {
+                    if let Ok(i) = atom.parse::<BigInt>() {
+                        Ok(i.into())
+                    } else if let Some(v) = variable(&atom) {
+                        Ok(Expr::Var(v))
+                    } else {
+                        bail!("Unknown atom at {offset}: {atom}")
+                    }
+                }
+            },
+            Lexeme::Close => bail!("Unexpected ')' at {offset}"),
+            Lexeme::Open => self.list(offset),
+        }
+    }
+
+    /// `(` の後を読む
+    fn list(&mut self, offset: usize) -> anyhow::Result<Expr> {
+        let head = match self.peek() {
+            Some(Lexeme::Atom(atom)) => atom.clone(),
+            _ => String::new(),
+        };
+        match head.as_str() {
+            "λ" | "\\" => {
+                self.next()?;
+                let var_offset = self.offset();
+                let var = self.atom()?;
+                let var = variable(&var)
+                    .with_context(|| format!("Expected variable at {var_offset}: got {var}"))?;
+                let dot_offset = self.offset();
+                if self.atom()? != "." {
+                    bail!("Expected '.' at {dot_offset}");
+                }
+                let body = self.expr()?;
+                self.close()?;
+                Ok(Expr::Lambda(var, body.into()))
+            }
+            "if" => {
+                self.next()?;
+                let Ok::<[Expr; 3], _>([c, t, e]) = self.args()?.try_into() else {
+                    bail!("if at {offset} takes 3 arguments");
+                };
+                Ok(Expr::If(c.into(), t.into(), e.into()))
+            }
+            head if unary_op(head).is_some() || binary_op(head).is_some() => {
+                self.next()?;
+                let mut args = self.args()?;
+                match (unary_op(head), binary_op(head), args.len()) {
+                    (Some(o), _, 1) => Ok(Expr::UnaryOp(o, args.remove(0).into())),
+                    (_, Some(o), 2) => {
+                        let r = args.pop().expect("unreachable");
+                        let l = args.pop().expect("unreachable");
+                        Ok(Expr::BinaryOp(o, l.into(), r.into()))
+                    }
+                    _ => bail!("Wrong number of arguments for {head} at {offset}"),
+                }
+            }
+            _ => {
+                // 関数適用 (f x y) は ((f x) y) とする
+                let mut args = self.args()?.into_iter();
+                let f = args
+                    .next()
+                    .with_context(|| format!("Empty list at {offset}"))?;
+                let mut args = args.peekable();
+                if args.peek().is_none() {
+                    bail!("No argument for application at {offset}");
+                }
+                Ok(args.fold(f, |f, x| {
+                    Expr::BinaryOp(BinaryOp::Apply, f.into(), x.into())
+                }))
+            }
+        }
+    }
+}
+
+pub fn parse(s: &str) -> anyhow::Result<Expr> {
+    let mut reader = Reader {
+        lexemes: lex(s)?,
+        pos: 0,
+        len: s.len(),
+    };
+    let expr = reader.expr()?;
+    if reader.peek().is_some() {
+        bail!("Unexpected input after expression at {}", reader.offset());
+    }
+    Ok(expr)
+}
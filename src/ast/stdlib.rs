@@ -0,0 +1,65 @@
+//! よく使うコンビネータを `Expr` として組み立てる
+//!
+//! アセンブラの組み込みマクロ (`src/assemble/prelude.asm`) と同じもの。
+//! どれも閉じた式なので `BinaryOp::Apply` で引数に適用して使う。
+
+use super::{
+    builder::{app, call, concat, div, drop, eq, fix, int, ite, lam, lam2, rem, string, sub, take},
+    Expr,
+};
+
+/// Y コンビネータ `λf. (λx. f (x x)) (λx. f (x x))`
+pub fn y() -> Expr {
+    lam(|f| {
+        let half = lam(|x| app(f.clone(), app(x.clone(), x)));
+        app(half.clone(), half)
+    })
+}
+
+/// 文字列 s を n 回繰り返す `λs. λn. ...`
+pub fn repeat() -> Expr {
+    fix(|r| {
+        lam2(|s, n| {
+            ite(
+                eq(n.clone(), int(0)),
+                string(""),
+                concat(s.clone(), call(r, [s, sub(n, int(1))])),
+            )
+        })
+    })
+}
+
+/// 非負整数 n の k 進数の桁を下の桁から畳み込む `λk. λf. λacc. λn. ...`
+///
+/// `f acc digit` が次の acc になる。n = 0 なら acc のまま。
+pub fn fold_digits() -> Expr {
+    lam2(|k, f| {
+        fix(|r| {
+            lam2(|acc, n| {
+                ite(
+                    eq(n.clone(), int(0)),
+                    acc.clone(),
+                    call(r, [call(f, [acc, rem(n.clone(), k.clone())]), div(n, k)]),
+                )
+            })
+        })
+    })
+}
+
+/// 文字列を逆順にする `λs. ...`
+pub fn reverse() -> Expr {
+    fix(|r| {
+        lam(|s| {
+            ite(
+                eq(s.clone(), string("")),
+                string(""),
+                concat(app(r, drop(int(1), s.clone())), take(int(1), s)),
+            )
+        })
+    })
+}
+
+/// s の i 文字目 (0 始まり) `λs. λi. ...`
+pub fn char_at() -> Expr {
+    lam2(|s, i| take(int(1), drop(i, s)))
+}
@@ -1,12 +1,35 @@
-use std::io::{self, Read};
+use std::{
+    io::{self, Read},
+    path::{Path, PathBuf},
+};
 
-use icfpc2024::assemble::assemble;
+use clap::Parser;
+use icfpc2024::assemble::Assembler;
 
+#[derive(Parser)]
+#[command(version, about, long_about = None)]
+struct AsmCli {
+    /// Path to the asm file (read from stdin if omitted)
+    /// e.g. data/lambdaman/lambdaman6.asm
+    input: Option<PathBuf>,
+}
+
+/// Usage: `cargo run --bin asm <<<'B. "Hello " "World!"'`
 fn main() -> anyhow::Result<()> {
-    let mut input = String::new();
-    io::stdin().read_to_string(&mut input)?;
+    let cli = AsmCli::parse();
 
-    let tokens = assemble(&input)?;
+    let mut assembler = Assembler::new();
+    let tokens = match cli.input {
+        Some(path) => assembler.assemble_file(&path)?,
+        None => {
+            let mut input = String::new();
+            io::stdin().read_to_string(&mut input)?;
+            assembler.assemble(&input, Path::new("."))?
+        }
+    };
+    for warning in assembler.warnings() {
+        eprintln!("warning: {warning}");
+    }
     print!("{}", tokens.join(" "));
 
     Ok(())
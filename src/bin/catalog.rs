@@ -0,0 +1,107 @@
+use std::{fs, path::PathBuf};
+
+use clap::Parser;
+use icfpc2024::catalog::{Catalog, Entry, Query};
+
+#[derive(Parser)]
+#[command(version, about, long_about = None)]
+struct CatalogCli {
+    /// Directory with the tasks and the local outputs
+    #[arg(long, default_value = "data")]
+    data: PathBuf,
+    /// e.g. lambdaman
+    #[arg(long)]
+    family: Option<String>,
+    /// e.g. lambdaman1
+    #[arg(long)]
+    task: Option<String>,
+    /// e.g. toga
+    #[arg(long)]
+    author: Option<String>,
+    /// e.g. beam
+    #[arg(long)]
+    method: Option<String>,
+    /// Only list solutions verified by the local simulators
+    #[arg(long)]
+    verified: bool,
+    /// Only list the best verified solution of each task
+    #[arg(long)]
+    best: bool,
+    /// Export the listed solutions to a JSON file
+    /// e.g. data/catalog.json
+    #[arg(long)]
+    json: Option<PathBuf>,
+    /// Print visualizer URLs of the best solutions instead of the list
+    /// e.g. http://localhost:8000/vis.html
+    #[arg(long)]
+    vis: Option<String>,
+}
+
+/// 可視化のページに渡す URL（英数字以外はパーセントエンコードする）
+fn vis_url(base: &str, entry: &Entry) -> anyhow::Result<String> {
+    let output = entry
+        .answer()?
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' => (b as char).to_string(),
+            _ => format!("%{b:02X}"),
+        })
+        .collect::<String>();
+    Ok(format!(
+        "{base}?type={}&seed={}&output={output}",
+        entry.family, entry.number
+    ))
+}
+
+/// Usage: `cargo run --release --bin catalog -- --family lambdaman --best --json data/catalog.json`
+fn main() -> anyhow::Result<()> {
+    let cli = CatalogCli::parse();
+    let query = Query {
+        family: cli.family,
+        task: cli.task,
+        author: cli.author,
+        method: cli.method,
+        verified: cli.verified || cli.best || cli.vis.is_some(),
+    };
+    let catalog = Catalog::scan(&cli.data, &query)?;
+    let entries = if cli.best || cli.vis.is_some() {
+        catalog
+            .tasks()
+            .iter()
+            .filter_map(|task| catalog.best(task).cloned())
+            .collect()
+    } else {
+        catalog.entries
+    };
+
+    if let Some(path) = &cli.json {
+        fs::write(path, format!("{}\n", serde_json::to_string(&entries)?))?;
+        eprintln!("Exported {} solutions to {}", entries.len(), path.display());
+    }
+    if let Some(base) = &cli.vis {
+        for entry in &entries {
+            if matches!(entry.family.as_str(), "lambdaman" | "spaceship") {
+                println!("{}\t{}", entry.task(), vis_url(base, entry)?);
+            }
+        }
+        return Ok(());
+    }
+    let show = |n: Option<u64>| n.map_or("-".to_owned(), |n| n.to_string());
+    for entry in &entries {
+        let mut line = format!(
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            entry.task(),
+            entry.author,
+            entry.method.as_deref().unwrap_or("-"),
+            show(entry.score),
+            show(entry.size()),
+            if entry.verified { "ok" } else { "-" },
+            entry.path.display()
+        );
+        if let Some(error) = &entry.error {
+            line += &format!("\t{error}");
+        }
+        println!("{line}");
+    }
+    Ok(())
+}
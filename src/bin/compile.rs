@@ -0,0 +1,34 @@
+use std::{
+    fs,
+    io::{self, Read},
+    path::PathBuf,
+};
+
+use clap::Parser;
+use icfpc2024::{compile, token};
+
+#[derive(Parser)]
+#[command(version, about, long_about = None)]
+struct CompileCli {
+    /// Path to the source file (read from stdin if omitted)
+    /// e.g. program.ml
+    input: Option<PathBuf>,
+}
+
+/// Usage: `cargo run --bin compile <<<'let rec f n = if n == 0 then 1 else n * f (n - 1) in f 10' | cargo run --bin eval`
+fn main() -> anyhow::Result<()> {
+    let cli = CompileCli::parse();
+
+    let src = match cli.input {
+        Some(path) => fs::read_to_string(path)?,
+        None => {
+            let mut input = String::new();
+            io::stdin().read_to_string(&mut input)?;
+            input
+        }
+    };
+    let tokens = compile::compile(&src)?;
+    print!("{}", token::encode(&tokens)?);
+
+    Ok(())
+}
@@ -0,0 +1,16 @@
+use std::io::{self, Read};
+
+use icfpc2024::{disassemble::disassemble, token};
+
+/// ICFP のプログラムをアセンブラのソースにする
+///
+/// Usage: `cargo run --bin disasm < data/efficiency/efficiency1.raw > efficiency1.asm`
+fn main() -> anyhow::Result<()> {
+    let mut input = String::new();
+    io::stdin().read_to_string(&mut input)?;
+
+    let tokens = token::decode_token_stream(input.trim())?;
+    print!("{}", disassemble(&tokens)?);
+
+    Ok(())
+}
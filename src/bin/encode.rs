@@ -1,13 +1,30 @@
 use std::io;
 
+use clap::Parser;
 use icfpc2024::token;
+use indicatif::ProgressBar;
+
+#[derive(Parser)]
+#[command(version, about, long_about = None)]
+struct EncodeCli {
+    /// Show the progress of encoding large integers (e.g. packed lambdaman paths) on stderr
+    #[arg(short, long)]
+    progress: bool,
+}
 
 /// Usage: `cargo run --bin encode <<<'12345'`
 fn main() -> anyhow::Result<()> {
+    let cli = EncodeCli::parse();
     let mut input = String::new();
     io::stdin().read_line(&mut input)?;
 
-    println!("{}", token::encode(&token::encode_string(input.trim())?)?);
+    let tokens = token::encode_string(input.trim())?;
+    let bar = if cli.progress {
+        ProgressBar::new(0)
+    } else {
+        ProgressBar::hidden()
+    };
+    println!("{}", token::encode_with_progress(&tokens, &bar)?);
 
     Ok(())
 }
@@ -1,17 +1,28 @@
-use std::path::PathBuf;
+use std::{
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
 
 use anyhow::{Context, Ok};
 use clap::{Parser, Subcommand, ValueEnum};
 use icfpc2024::{
-    assemble::assemble,
+    assemble::Assembler,
+    catalog::{Catalog, Query},
+    client::{log::timestamp, Client, ClientArgs},
+    scoreboard::{self, Change, FamilySnapshot, Snapshot, Table, DEFAULT_SNAPSHOTS},
+    tasks,
+    threed::{self, Grid},
     token::{self, encode_string, Token},
 };
+use num_bigint::BigInt;
 
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
 struct IcfpcCli {
     #[command(subcommand)]
     commands: Command,
+    #[command(flatten)]
+    client: ClientArgs,
 }
 
 #[derive(Subcommand)]
@@ -30,6 +41,34 @@ enum Command {
         #[arg(short, long)]
         save: Option<PathBuf>,
     },
+    /// Fetch the scoreboards and our scores, and compare them with the last snapshot
+    Scoreboard {
+        /// Families to fetch (all families in `get index` if omitted)
+        /// e.g. lambdaman spaceship
+        families: Vec<String>,
+        /// Our team name, to show our rank
+        #[arg(long)]
+        team: Option<String>,
+        /// JSONL file the snapshots are appended to
+        #[arg(long, default_value = DEFAULT_SNAPSHOTS)]
+        snapshots: PathBuf,
+        /// Directory with the local outputs
+        #[arg(long, default_value = "data")]
+        data: PathBuf,
+    },
+    /// Submit the best local solution of each task unless our server score is already as good
+    SubmitBest {
+        /// Families to submit
+        /// e.g. lambdaman
+        #[arg(default_values_t = ["lambdaman".to_owned(), "spaceship".to_owned(), "3d".to_owned()])]
+        families: Vec<String>,
+        /// Directory with the local outputs
+        #[arg(long, default_value = "data")]
+        data: PathBuf,
+        /// Only print what would be submitted
+        #[arg(long)]
+        dry_run: bool,
+    },
 }
 
 #[derive(Clone, Debug, ValueEnum)]
@@ -45,6 +84,7 @@ enum Task {
 }
 
 async fn submit_solution(
+    client: &Client,
     output: PathBuf,
     raw: bool,
     task: Task,
@@ -64,12 +104,18 @@ async fn submit_solution(
     eprintln!("problem_name: {}", problem_name);
 
     let request = if raw {
-        assemble(&text)?.join(" ")
+        let mut assembler = Assembler::new();
+        let tokens = assembler.assemble_file(&output)?;
+        for warning in assembler.warnings() {
+            eprintln!("warning: {warning}");
+        }
+        tokens.join(" ")
     } else {
         let tokens = match task {
             Task::Lambdaman => encode_lambdaman(problem_name, &text)?,
             Task::Spaceship => encode_spaceship(problem_name, &text)?,
-            _ => todo!(),
+            Task::Threed => encode_threed(problem_name, &text, &output)?,
+            Task::Efficieny => encode_efficiency(problem_name, &text)?,
         };
         token::encode(&tokens)?
     };
@@ -79,30 +125,196 @@ async fn submit_solution(
     }
 
     eprintln!("Submitting '{output_file_name}' for '{problem_name}' to the server...");
-    let tokens = icfpc2024::send(request).await?;
+    let tokens = client.send(&request).await?;
     let result = icfpc2024::eval_tokens(&tokens)?;
     Ok(result)
 }
 
+async fn fetch_scoreboard(
+    client: &Client,
+    families: Vec<String>,
+    team: Option<String>,
+    snapshots: PathBuf,
+    data: PathBuf,
+) -> anyhow::Result<()> {
+    let families = if families.is_empty() {
+        tasks::families(&client.request("get index").await?)
+    } else {
+        families
+    };
+    let previous = scoreboard::load_snapshots(&snapshots)?.pop();
+    let mut snapshot = Snapshot {
+        taken_at: timestamp(SystemTime::now()),
+        families: vec![],
+    };
+    for family in families {
+        let board = client.request(&format!("get scoreboard {family}")).await?;
+        let page = client.request(&format!("get {family}")).await?;
+        snapshot.families.push(FamilySnapshot {
+            family,
+            scoreboard: Table::parse(&board),
+            scores: scoreboard::task_scores(&page),
+        });
+    }
+    scoreboard::append_snapshot(&snapshots, &snapshot)?;
+
+    for current in &snapshot.families {
+        let last = previous.as_ref().and_then(|p| p.family(&current.family));
+        println!("== {} ==", current.family);
+        if let Some(team) = &team {
+            let rank = |family: &FamilySnapshot| family.scoreboard.rank(team);
+            match (rank(current), last.and_then(rank)) {
+                (Some(rank), Some(was)) => println!("rank: {rank} (was {was})"),
+                (Some(rank), None) => println!("rank: {rank}"),
+                (None, _) => println!("rank: {team} is not on the scoreboard"),
+            }
+        }
+        for change in scoreboard::changes(last, current) {
+            match change {
+                Change::Solved { task, score } => println!("{task}: solved with {score}"),
+                Change::Improved { task, from, to } => println!("{task}: {from} -> {to}"),
+                Change::Worsened { task, from, to } => println!("{task}: {from} -> {to} (worse)"),
+            }
+        }
+        let query = Query {
+            family: Some(current.family.clone()),
+            verified: true,
+            ..Default::default()
+        };
+        let catalog = Catalog::scan(&data, &query)?;
+        for score in &current.scores {
+            let Some((local, local_score)) = catalog
+                .best(&score.task)
+                .and_then(|local| Some((local, local.score?)))
+            else {
+                continue;
+            };
+            match score.ours {
+                Some(ours) if local_score >= ours => {}
+                ours => println!(
+                    "{}: local {} beats {} ({})",
+                    score.task,
+                    local_score,
+                    ours.map_or("unsolved".to_owned(), |n| n.to_string()),
+                    local.path.display()
+                ),
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn submit_best(
+    client: &Client,
+    families: Vec<String>,
+    data: PathBuf,
+    dry_run: bool,
+) -> anyhow::Result<()> {
+    for family in families {
+        let page = client.request(&format!("get {family}")).await?;
+        let query = Query {
+            family: Some(family.clone()),
+            verified: true,
+            ..Default::default()
+        };
+        let catalog = Catalog::scan(&data, &query)?;
+        for task_score in scoreboard::task_scores(&page) {
+            let task = &task_score.task;
+            let Some((best, score, request)) = catalog
+                .best(task)
+                .and_then(|best| Some((best, best.score?, best.request.as_ref()?)))
+            else {
+                eprintln!("{task}: no valid local solution");
+                continue;
+            };
+            let ours = task_score
+                .ours
+                .map_or("unsolved".to_owned(), |n| n.to_string());
+            if task_score.ours.is_some_and(|ours| ours <= score) {
+                eprintln!("{task}: server score {ours} is as good as {score}");
+                continue;
+            }
+            if dry_run {
+                println!(
+                    "{task}: would submit {} ({score} < {ours})",
+                    best.path.display()
+                );
+                continue;
+            }
+            eprintln!(
+                "Submitting '{}' for '{task}' ({score} < {ours})...",
+                best.path.display()
+            );
+            // 確かめたときに作ったリクエストをそのまま送る
+            let result = icfpc2024::eval_tokens(&client.send(request).await?)?;
+            println!("{task}: {result}");
+        }
+    }
+    Ok(())
+}
+
 fn encode_lambdaman(problem_name: &str, text: &str) -> anyhow::Result<Vec<Token>> {
-    let cmd = format!("solve {problem_name} {text}");
+    let cmd = format!("solve {problem_name} {}", text.trim());
     eprintln!("problem_name: {}", problem_name);
     // lambdamanに高速なやつ
     encode_string(&cmd)
 }
 
 fn encode_spaceship(problem_name: &str, text: &str) -> anyhow::Result<Vec<Token>> {
-    let mut tokens = vec![
-        token::Token::BinaryOp(token::BinaryOp::Concat),
-        token::Token::String(format!("solve {problem_name} ")),
-    ];
-    tokens.extend(token::encode_string(text)?);
-    Ok(tokens)
+    let cmd = format!("solve {problem_name} {}", text.trim());
+    // 数字の連続部分は整数に詰めて送る
+    encode_string(&cmd)
+}
+
+fn encode_threed(problem_name: &str, text: &str, output: &Path) -> anyhow::Result<Vec<Token>> {
+    // `solve 3dN` の行はあってもなくてもよい
+    let grid = threed::strip_header(text).trim_end();
+    let parsed = grid
+        .parse::<Grid>()
+        .and_then(|parsed| parsed.check().map(|_| parsed))
+        .with_context(|| format!("Invalid 3d program for {problem_name}"))?;
+    // 課題文（隣か `data/3d/` にある）の例を全部通してから送る
+    let file_name = format!("{problem_name}.in");
+    let statement = [
+        output.with_file_name(&file_name),
+        Path::new("data/3d").join(&file_name),
+    ]
+    .into_iter()
+    .find(|path| path.exists());
+    if let Some(statement) = statement {
+        let examples = threed::examples(&std::fs::read_to_string(&statement)?)?;
+        let score = threed::validate(&parsed, &examples, threed::MAX_STEPS)
+            .with_context(|| format!("Wrong 3d program for {problem_name}"))?;
+        eprintln!(
+            "Passed {} examples with volume {} ({} ticks, {}x{})",
+            examples.len(),
+            score.volume,
+            score.ticks,
+            score.width,
+            score.height
+        );
+    } else {
+        eprintln!(
+            "warning: {file_name} is neither next to {} nor in data/3d; submitting without checking the examples",
+            output.display()
+        );
+    }
+    let cmd = format!("solve {problem_name}\n{grid}");
+    encode_string(&cmd)
+}
+
+fn encode_efficiency(problem_name: &str, text: &str) -> anyhow::Result<Vec<Token>> {
+    let answer = text
+        .trim()
+        .parse::<BigInt>()
+        .with_context(|| format!("Expected an integer answer for {problem_name}"))?;
+    encode_string(&format!("solve {problem_name} {answer}"))
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let cli = IcfpcCli::parse();
+    let client = cli.client.client()?;
     match cli.commands {
         Command::Submit {
             output,
@@ -110,9 +322,24 @@ async fn main() -> anyhow::Result<()> {
             raw,
             save,
         } => {
-            let result = submit_solution(output, raw, task, save).await?;
+            let result = submit_solution(&client, output, raw, task, save).await?;
             println!("{}", result);
         }
+        Command::Scoreboard {
+            families,
+            team,
+            snapshots,
+            data,
+        } => {
+            fetch_scoreboard(&client, families, team, snapshots, data).await?;
+        }
+        Command::SubmitBest {
+            families,
+            data,
+            dry_run,
+        } => {
+            submit_best(&client, families, data, dry_run).await?;
+        }
     }
     Ok(())
 }
@@ -0,0 +1,48 @@
+use std::path::PathBuf;
+
+use anyhow::Context;
+use clap::Parser;
+use icfpc2024::{lambdaman::Map, token};
+
+#[derive(Parser)]
+#[command(version, about, long_about = None)]
+struct LambdamanRandomWalkCli {
+    /// Path to the input file
+    /// e.g. data/lambdaman/lambdaman4.in
+    #[arg(short, long)]
+    input: PathBuf,
+
+    /// Number of seeds to try for each parameter set
+    #[arg(short, long, default_value = "16")]
+    seeds: u64,
+
+    /// Save the encoded program to file
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+/// Usage: `cargo run --release --bin lambdaman_random_walk -- -i data/lambdaman/lambdaman4.in`
+fn main() -> anyhow::Result<()> {
+    let cli = LambdamanRandomWalkCli::parse();
+    let map: Map = std::fs::read_to_string(&cli.input)?.parse()?;
+    let problem_file_name = cli
+        .input
+        .file_name()
+        .context("Expected file name")?
+        .to_string_lossy();
+    let problem_name = problem_file_name
+        .split('.')
+        .next()
+        .context("Expected file name")?;
+
+    let walk = token::random_walk::search(&map, cli.seeds).context("No random walk found")?;
+    eprintln!("{walk:?}");
+    let request = token::encode(&walk.tokens(&format!("solve {problem_name} ")))?;
+    eprintln!("Program size: {}", request.len());
+
+    match cli.output {
+        Some(file) => std::fs::write(file, request)?,
+        None => println!("{request}"),
+    }
+    Ok(())
+}
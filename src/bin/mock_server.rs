@@ -0,0 +1,30 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+use icfpc2024::{client::REQUESTS_PER_MINUTE, mock_server::MockServer};
+
+#[derive(Parser)]
+#[command(version, about, long_about = None)]
+struct MockServerCli {
+    /// Port to listen on
+    #[arg(long, default_value_t = 8000)]
+    port: u16,
+    /// Directory with the downloaded tasks
+    /// e.g. data
+    #[arg(long, default_value = "data")]
+    data: PathBuf,
+    /// Requests accepted per minute before answering with an empty body
+    #[arg(long, default_value_t = REQUESTS_PER_MINUTE as usize)]
+    rate_limit: usize,
+}
+
+/// Usage: `cargo run --bin mock_server -- --port 8000` and then `cargo run --bin shell -- --url http://localhost:8000/communicate --token dummy`
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let cli = MockServerCli::parse();
+
+    let server = MockServer::new(cli.data).with_rate_limit(cli.rate_limit);
+    let handle = server.bind(&format!("127.0.0.1:{}", cli.port)).await?;
+    eprintln!("Listening on {}", handle.url());
+    handle.wait().await
+}
@@ -0,0 +1,16 @@
+use std::io::{self, Read};
+
+use icfpc2024::{ast::sexp, token};
+
+/// `parse` が出力する S 式を ICFP のトークン列に戻す
+///
+/// Usage: `cargo run --bin sexp <<<'(. "Hello" " World!")' | cargo run --bin eval`
+fn main() -> anyhow::Result<()> {
+    let mut input = String::new();
+    io::stdin().read_to_string(&mut input)?;
+
+    let ast = sexp::parse(&input)?;
+    println!("{}", token::encode(&ast.to_tokens())?);
+
+    Ok(())
+}
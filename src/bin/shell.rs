@@ -1,36 +1,254 @@
-use rustyline::error::ReadlineError;
-use rustyline::{CompletionType, Config, DefaultEditor, EditMode};
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
 
-use icfpc2024::token;
+use clap::Parser;
+use itertools::Itertools;
+use rustyline::{
+    completion::{Completer, Pair},
+    error::ReadlineError,
+    highlight::Highlighter,
+    hint::Hinter,
+    history::DefaultHistory,
+    validate::{ValidationContext, ValidationResult, Validator},
+    CompletionType, Config, EditMode, Editor, Helper,
+};
 
+use icfpc2024::{
+    assemble,
+    client::{Client, ClientArgs},
+    eval_encoded, token,
+};
+
+#[derive(Parser)]
+#[command(version, about, long_about = None)]
+struct ShellCli {
+    #[command(flatten)]
+    client: ClientArgs,
+    /// Directory with the downloaded tasks (used for completing task names)
+    /// e.g. data
+    #[arg(long, default_value = "data")]
+    data: PathBuf,
+    /// History file (defaults to ~/.icfpc_history)
+    #[arg(long)]
+    history: Option<PathBuf>,
+}
+
+const COMMANDS: [&str; 3] = ["get", "solve", "echo"];
+
+const META_COMMANDS: [(&str, &str); 8] = [
+    (":raw", "send an ICFP program as is"),
+    (":asm", "assemble and send"),
+    (":eval", "evaluate an ICFP program locally"),
+    (":decode", "decode an ICFP token stream without evaluating"),
+    (":save", "save the last response to a file"),
+    (":history", "show the history"),
+    (":help", "show this help"),
+    (":quit", "exit"),
+];
+
+/// 行頭のコマンドと `get` `solve` の後の課題名を補完し、`\` で終わる行は次の行に続ける
+struct ShellHelper {
+    tasks: Vec<String>,
+}
+
+impl ShellHelper {
+    /// `data/{family}/{task}.raw` から課題名を集める
+    fn new(data: &Path) -> Self {
+        let mut tasks = vec!["index".to_owned()];
+        for family in fs::read_dir(data).into_iter().flatten().flatten() {
+            let family_name = family.file_name().to_string_lossy().into_owned();
+            let mut numbers = fs::read_dir(family.path())
+                .into_iter()
+                .flatten()
+                .flatten()
+                .filter_map(|file| {
+                    let name = file.file_name().to_string_lossy().into_owned();
+                    name.strip_suffix(".raw")?
+                        .strip_prefix(&family_name)?
+                        .parse::<u32>()
+                        .ok()
+                })
+                .collect_vec();
+            if numbers.is_empty() {
+                continue;
+            }
+            numbers.sort();
+            tasks.extend(numbers.iter().map(|n| format!("{family_name}{n}")));
+            tasks.push(family_name);
+        }
+        ShellHelper { tasks }
+    }
+}
+
+impl Completer for ShellHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &rustyline::Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let line = &line[..pos];
+        let start = line.rfind(char::is_whitespace).map_or(0, |i| i + 1);
+        let word = &line[start..];
+        let candidates = if start == 0 {
+            COMMANDS
+                .iter()
+                .copied()
+                .chain(META_COMMANDS.iter().map(|(name, _)| *name))
+                .map(str::to_owned)
+                .collect_vec()
+        } else if matches!(
+            line[..start].split_whitespace().collect_vec()[..],
+            ["get" | "solve"]
+        ) {
+            self.tasks.clone()
+        } else {
+            vec![]
+        };
+        let pairs = candidates
+            .into_iter()
+            .filter(|candidate| candidate.starts_with(word))
+            .map(|candidate| Pair {
+                display: candidate.clone(),
+                replacement: format!("{candidate} "),
+            })
+            .collect();
+        Ok((start, pairs))
+    }
+}
+
+impl Hinter for ShellHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ShellHelper {}
+
+impl Validator for ShellHelper {
+    fn validate(&self, ctx: &mut ValidationContext) -> rustyline::Result<ValidationResult> {
+        Ok(if ctx.input().ends_with('\\') {
+            ValidationResult::Incomplete
+        } else {
+            ValidationResult::Valid(None)
+        })
+    }
+}
+
+impl Helper for ShellHelper {}
+
+/// 行末の `\` を取り除いて改行でつなぐ
+fn join_lines(input: &str) -> String {
+    input.replace("\\\n", "\n").trim().to_owned()
+}
+
+struct Shell {
+    client: Client,
+    /// 直前の応答（`:save` で保存する）
+    last: Option<String>,
+}
+
+impl Shell {
+    async fn run(
+        &mut self,
+        input: &str,
+        rl: &Editor<ShellHelper, DefaultHistory>,
+    ) -> anyhow::Result<()> {
+        let (command, rest) = input
+            .split_once(char::is_whitespace)
+            .map_or((input, ""), |(command, rest)| (command, rest.trim()));
+        let output = match command {
+            // 応答は深いことがあるので大きなスタックで評価する（溢れると履歴を保存せずに落ちる）
+            ":raw" => eval_encoded(&self.client.send_raw(rest).await?)?,
+            ":asm" => {
+                let program = assemble::assemble(rest)?.join(" ");
+                eval_encoded(&self.client.send_raw(&program).await?)?
+            }
+            ":eval" => eval_encoded(rest)?,
+            ":decode" => token::decode_token_stream(rest)?.into_iter().join(" "),
+            ":save" => {
+                let last = self
+                    .last
+                    .as_ref()
+                    .ok_or_else(|| anyhow::anyhow!("No response yet"))?;
+                fs::write(rest, last)?;
+                eprintln!("Saved to {rest}");
+                return Ok(());
+            }
+            ":history" => {
+                for (i, entry) in rl.history().iter().enumerate() {
+                    println!("{:5}  {entry}", i + 1);
+                }
+                return Ok(());
+            }
+            ":help" => {
+                for (name, help) in META_COMMANDS {
+                    println!("{name:10}{help}");
+                }
+                println!("Other lines are sent as strings, e.g. `get index`. End a line with \\ to continue it.");
+                return Ok(());
+            }
+            _ if command.starts_with(':') => {
+                anyhow::bail!("Unknown command: {command} (see :help)")
+            }
+            _ => self.client.request(input).await?,
+        };
+        println!("{output}");
+        self.last = Some(output);
+        Ok(())
+    }
+}
+
+/// Usage: `cargo run --bin shell`, then e.g. `get index`, `:eval B. S4% S34`, `:save lambdaman1.txt`
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    let mut rl = DefaultEditor::with_config(
+    let cli = ShellCli::parse();
+    let client = cli.client.client()?;
+    let history = cli.history.unwrap_or_else(|| {
+        PathBuf::from(env::var("HOME").unwrap_or_else(|_| ".".to_owned())).join(".icfpc_history")
+    });
+
+    let mut rl = Editor::with_config(
         Config::builder()
             .history_ignore_space(true)
             .completion_type(CompletionType::List)
             .edit_mode(EditMode::Emacs)
             .build(),
     )?;
+    rl.set_helper(Some(ShellHelper::new(&cli.data)));
+    // 初回はまだない
+    let _ = rl.load_history(&history);
+
+    let mut shell = Shell { client, last: None };
     loop {
         let input = match rl.readline("❯ ") {
             Ok(line) => {
                 rl.add_history_entry(line.as_str())?;
-                line
+                join_lines(&line)
             }
             Err(ReadlineError::Eof) => {
                 break;
             }
+            Err(ReadlineError::Interrupted) => {
+                continue;
+            }
             Err(err) => {
                 return Err(err.into());
             }
         };
-        let tokens = token::encode_string(input.trim())?;
-        let request = token::encode(&tokens)?;
-        let tokens = icfpc2024::send(request).await?;
-        let result = icfpc2024::eval_tokens(&tokens)?;
-        println!("{}", result);
+        if input.is_empty() {
+            continue;
+        }
+        if input == ":quit" {
+            break;
+        }
+        if let Err(err) = shell.run(&input, &rl).await {
+            eprintln!("Error: {err:#}");
+        }
     }
+    rl.save_history(&history)?;
 
     Ok(())
 }
@@ -1,43 +1,67 @@
-use icfpc2024::{eval_tokens, send, token};
-use itertools::Itertools;
+use std::{fs, path::PathBuf, time::SystemTime};
 
+use clap::Parser;
+use icfpc2024::{
+    client::{log::timestamp, ClientArgs},
+    tasks::{self, Manifest, ManifestEntry},
+};
+
+#[derive(Parser)]
+#[command(version, about, long_about = None)]
+struct TaskDownloaderCli {
+    #[command(flatten)]
+    client: ClientArgs,
+    /// Families to download (all families in `get index` if omitted)
+    /// e.g. lambdaman spaceship
+    families: Vec<String>,
+    /// Directory to save the tasks to
+    #[arg(long, default_value = "data")]
+    data: PathBuf,
+    /// Download tasks that are already saved again
+    #[arg(long)]
+    force: bool,
+}
+
+/// Usage: `cargo run --bin task_downloader -- efficiency --force`
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    let problems = ["lambdaman", "spaceship", "3d"];
-    for problem in problems.iter() {
-        eprintln!("Downloading problem: {}", problem);
-        let problem_command = format!("get {}", problem);
-        let request = token::encode(&[token::Token::String(problem_command)])?;
-        let tokens = send(request).await?;
-        let text = eval_tokens(&tokens)?;
-        let problem_statement = token::decode_token_stream(&text)?.into_iter().join("");
-        for x in 1.. {
-            // e.g. lambdaman1
-            let task = format!("{problem}{x}");
-            if !problem_statement.contains(&task) {
-                break;
+    let cli = TaskDownloaderCli::parse();
+    let client = cli.client.client()?;
+
+    let families = if cli.families.is_empty() {
+        tasks::families(&client.request("get index").await?)
+    } else {
+        cli.families
+    };
+    let mut manifest = Manifest::load(&cli.data)?;
+    for family in &families {
+        eprintln!("Downloading family: {family}");
+        let page = client.request(&format!("get {family}")).await?;
+        let dir = cli.data.join(family);
+        fs::create_dir_all(&dir)?;
+        for task in tasks::tasks(family, &page) {
+            // e.g. data/lambdaman/lambdaman1.raw
+            let raw_file = dir.join(format!("{task}.raw"));
+            if raw_file.exists() && !cli.force {
+                eprintln!("Skipping {task}: {} exists", raw_file.display());
+                continue;
             }
-            eprintln!("Downloading task: {}", task);
-            let task_command = format!("get {}", task);
-            let request = token::encode(&[token::Token::String(task_command)])?;
-            let tokens = send(request).await?;
-            let text = eval_tokens(&tokens)?;
-            // data/{problem}/{task}.raw
-            // data/{problem}/{task}.in
-            let task_raw_file = format!("data/{problem}/{task}.raw");
-            std::fs::write(&task_raw_file, text.clone())?;
-            eprintln!("Task Raw data saved to: {}", task_raw_file);
-            let task_file = format!("data/{problem}/{task}.in");
-            if let Ok(text) =
-                token::decode_token_stream(&text).map(|tokens| tokens.into_iter().join(""))
-            {
-                std::fs::write(&task_file, text)?;
-                eprintln!("Task data saved to: {}", task_file);
-            } else {
-                eprintln!("Failed to save task data to: {}", task_file);
+            eprintln!("Downloading task: {task}");
+            let downloaded = tasks::download(&client, family, &task, &dir).await?;
+            eprintln!("Task raw data saved to: {}", downloaded.raw_file.display());
+            match &downloaded.decoded_file {
+                Ok(path) => eprintln!("Task data saved to: {}", path.display()),
+                Err(err) => eprintln!("Failed to save task data for {task}: {err:#}"),
             }
-            // sleep 4 second
-            tokio::time::sleep(std::time::Duration::from_secs(4)).await;
+            manifest.insert(
+                &task,
+                ManifestEntry {
+                    family: family.clone(),
+                    fetched_at: timestamp(SystemTime::now()),
+                    size: downloaded.raw.len(),
+                },
+            );
+            manifest.save()?;
         }
     }
 
@@ -0,0 +1,37 @@
+use std::{fs, path::PathBuf};
+
+use clap::Parser;
+use icfpc2024::threed::{Grid, Simulator, MAX_STEPS};
+use num_bigint::BigInt;
+
+#[derive(Parser)]
+#[command(version, about, long_about = None)]
+struct ThreedCli {
+    /// Path to the 3d program
+    /// e.g. data/3d/3d1.kaku.out
+    program: PathBuf,
+    /// Value of A
+    #[arg(short, default_value = "0", allow_negative_numbers = true)]
+    a: BigInt,
+    /// Value of B
+    #[arg(short, default_value = "0", allow_negative_numbers = true)]
+    b: BigInt,
+    /// Give up after this many ticks
+    #[arg(long, default_value_t = MAX_STEPS)]
+    max_ticks: usize,
+}
+
+/// Usage: `cargo run --release --bin threed -- data/3d/3d1.kaku.out -a 5`
+fn main() -> anyhow::Result<()> {
+    let cli = ThreedCli::parse();
+    let grid = fs::read_to_string(&cli.program)?.parse::<Grid>()?;
+    let outcome = Simulator::new(&grid, &cli.a, &cli.b)
+        .with_max_steps(cli.max_ticks)
+        .run()?;
+    println!("{}", outcome.answer);
+    eprintln!(
+        "ticks: {}, steps: {}, size: {}x{}, volume: {}",
+        outcome.ticks, outcome.steps, outcome.width, outcome.height, outcome.volume
+    );
+    Ok(())
+}
@@ -0,0 +1,62 @@
+use std::{
+    fs,
+    io::{self, Read},
+    path::PathBuf,
+};
+
+use clap::Parser;
+use icfpc2024::threed::{self, compile, MAX_STEPS};
+
+#[derive(Parser)]
+#[command(version, about, long_about = None)]
+struct ThreedCompileCli {
+    /// Path to the source file (read from stdin if omitted)
+    /// e.g. factorial.txt
+    input: Option<PathBuf>,
+    /// Check the grid against the examples of this task
+    /// e.g. 3d1
+    #[arg(long)]
+    task: Option<String>,
+    /// Directory with the task statements
+    #[arg(long, default_value = "data")]
+    data: PathBuf,
+    /// Write the grid to this file instead of stdout
+    /// e.g. data/3d/3d1.kaku.compiled.out
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+/// Usage: `cargo run --release --bin threed_compile -- factorial.txt --task 3d1`
+fn main() -> anyhow::Result<()> {
+    let cli = ThreedCompileCli::parse();
+
+    let src = match cli.input {
+        Some(path) => fs::read_to_string(path)?,
+        None => {
+            let mut input = String::new();
+            io::stdin().read_to_string(&mut input)?;
+            input
+        }
+    };
+    let grid = compile::compile(&src)?;
+    eprintln!("Compiled to {}x{}", grid.width(), grid.height());
+    if let Some(task) = &cli.task {
+        let statement = fs::read_to_string(cli.data.join("3d").join(format!("{task}.in")))?;
+        let examples = threed::examples(&statement)?;
+        let score = threed::validate(&grid, &examples, MAX_STEPS)?;
+        eprintln!(
+            "Passed {} examples with volume {} (ticks: {}, size: {}x{})",
+            examples.len(),
+            score.volume,
+            score.ticks,
+            score.width,
+            score.height
+        );
+    }
+    match cli.output {
+        Some(path) => fs::write(path, grid.to_string())?,
+        None => print!("{grid}"),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,77 @@
+use std::{fs, path::PathBuf};
+
+use anyhow::Context;
+use clap::Parser;
+use icfpc2024::{
+    catalog,
+    threed::{self, optimize, Grid},
+};
+
+#[derive(Parser)]
+#[command(version, about, long_about = None)]
+struct ThreedOptimizeCli {
+    /// Path to the 3d program
+    /// e.g. data/3d/3d1.kaku.out
+    program: PathBuf,
+    /// Task whose examples the program must pass (guessed from the file name if omitted)
+    /// e.g. 3d1
+    #[arg(long)]
+    task: Option<String>,
+    /// Directory with the task statements
+    #[arg(long, default_value = "data")]
+    data: PathBuf,
+    /// Give up after checking this many candidates
+    #[arg(long, default_value_t = 10_000)]
+    max_evaluations: usize,
+    /// Save the result as data/3d/<task>.<author>.<volume>.out
+    /// e.g. kaku
+    #[arg(long)]
+    author: Option<String>,
+}
+
+/// Usage: `cargo run --release --bin threed_optimize -- data/3d/3d1.kaku.out --author kaku`
+fn main() -> anyhow::Result<()> {
+    let cli = ThreedOptimizeCli::parse();
+    let task = match cli.task {
+        Some(task) => task,
+        None => {
+            let file_name = cli
+                .program
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy();
+            catalog::parse_name(&file_name)
+                .context("Cannot guess the task from the file name; specify --task")?
+                .task
+        }
+    };
+    let grid = fs::read_to_string(&cli.program)?.parse::<Grid>()?;
+    let statement = fs::read_to_string(cli.data.join("3d").join(format!("{task}.in")))?;
+    let examples = threed::examples(&statement)?;
+    let before = threed::validate(&grid, &examples, threed::MAX_STEPS)?;
+
+    let optimized = optimize::optimize(&grid, &examples, cli.max_evaluations)?;
+    for (m, volume) in &optimized.moves {
+        eprintln!("{m}: {volume}");
+    }
+    let score = &optimized.score;
+    eprintln!(
+        "volume: {} -> {} (ticks: {}, size: {}x{}), {} candidates checked",
+        before.volume, score.volume, score.ticks, score.width, score.height, optimized.evaluations
+    );
+    match cli.author {
+        Some(_) if score.volume >= before.volume => {
+            eprintln!("Not saved because the volume did not improve");
+        }
+        Some(author) => {
+            let path = cli
+                .data
+                .join("3d")
+                .join(format!("{task}.{author}.{}.out", score.volume));
+            fs::write(&path, optimized.grid.to_string())?;
+            eprintln!("Saved to {}", path.display());
+        }
+        None => print!("{}", optimized.grid),
+    }
+    Ok(())
+}
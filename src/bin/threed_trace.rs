@@ -0,0 +1,136 @@
+use std::{fs, path::PathBuf};
+
+use clap::Parser;
+use icfpc2024::threed::{Board, Grid, Simulator, Tick, MAX_STEPS};
+use itertools::Itertools;
+use num_bigint::BigInt;
+use serde::Serialize;
+
+#[derive(Parser)]
+#[command(version, about, long_about = None)]
+struct ThreedTraceCli {
+    /// Path to the 3d program
+    /// e.g. data/3d/3d1.kaku.out
+    program: PathBuf,
+    /// Value of A
+    #[arg(short, default_value = "0", allow_negative_numbers = true)]
+    a: BigInt,
+    /// Value of B
+    #[arg(short, default_value = "0", allow_negative_numbers = true)]
+    b: BigInt,
+    /// First step to print (steps count every tick run, including ones undone by `@`)
+    #[arg(long, default_value_t = 1)]
+    start: usize,
+    /// Stop after printing this many steps
+    #[arg(long)]
+    count: Option<usize>,
+    /// Print the trace as JSON for the visualizer
+    #[arg(long)]
+    json: bool,
+    /// Give up after this many ticks
+    #[arg(long, default_value_t = MAX_STEPS)]
+    max_ticks: usize,
+}
+
+/// `--json` で出力する 1 step（tick のあとの盤面つき）
+#[derive(Serialize)]
+struct Step {
+    step: usize,
+    #[serde(flatten)]
+    tick: Tick,
+    board: Board,
+}
+
+#[derive(Serialize)]
+struct Trace {
+    a: String,
+    b: String,
+    initial: Option<Board>,
+    steps: Vec<Step>,
+    answer: Option<String>,
+    error: Option<String>,
+}
+
+/// 左上が `(0, 0)` でなければ位置も書く
+fn print_board(board: &Board) {
+    if let Some(((x, y), _)) = board.bounds().filter(|&(min, _)| min != (0, 0)) {
+        println!("from ({x}, {y})");
+    }
+    println!("{board}");
+}
+
+/// Usage: `cargo run --release --bin threed_trace -- data/3d/3d1.kaku.out -a 5 --start 10 --count 3`
+fn main() -> anyhow::Result<()> {
+    let cli = ThreedTraceCli::parse();
+    let grid = fs::read_to_string(&cli.program)?.parse::<Grid>()?;
+    let mut simulator = Simulator::new(&grid, &cli.a, &cli.b).with_max_steps(cli.max_ticks);
+
+    let initial = (cli.start <= 1).then(|| simulator.board().clone());
+    if let (Some(board), false) = (&initial, cli.json) {
+        println!("tick 1");
+        print_board(board);
+    }
+    let mut steps = vec![];
+    let mut answer = None;
+    let mut error = None;
+    let end = cli
+        .count
+        .map_or(usize::MAX, |count| cli.start.max(1) + count);
+    while simulator.steps() + 1 < end {
+        let step = simulator.steps() + 1;
+        let tick = match simulator.step() {
+            Ok(tick) => tick,
+            Err(err) => {
+                // 同じマスへの違う書き込みもここで止まる
+                error = Some(err.context(format!("Step {step}")));
+                break;
+            }
+        };
+        if step >= cli.start {
+            if cli.json {
+                steps.push(Step {
+                    step,
+                    tick: tick.clone(),
+                    board: simulator.board().clone(),
+                });
+            } else {
+                let to = if tick.is_warp() {
+                    format!("back to tick {} (time warp)", tick.to)
+                } else {
+                    format!("tick {}", tick.to)
+                };
+                println!("step {step}: tick {} -> {to}", tick.from);
+                let writes = tick
+                    .writes
+                    .iter()
+                    .map(|((x, y), cell)| format!("({x}, {y}) = {cell}"))
+                    .join(", ");
+                println!("writes: {writes}");
+                if let Some(submitted) = &tick.submitted {
+                    println!("submitted: {submitted}");
+                }
+                print_board(simulator.board());
+            }
+        }
+        if tick.submitted.is_some() {
+            answer = tick.submitted;
+            break;
+        }
+    }
+
+    if cli.json {
+        let trace = Trace {
+            a: cli.a.to_string(),
+            b: cli.b.to_string(),
+            initial,
+            steps,
+            answer: answer.as_ref().map(BigInt::to_string),
+            error: error.as_ref().map(|err| format!("{err:#}")),
+        };
+        println!("{}", serde_json::to_string(&trace)?);
+    }
+    match error {
+        Some(err) => Err(err),
+        None => Ok(()),
+    }
+}
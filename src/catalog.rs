@@ -0,0 +1,232 @@
+//! `data/` にある手元の解の一覧
+//!
+//! 解の情報はファイル名 `<task>.<author>[.<method>].out` だけなので、
+//! そこから課題・作者・方法を読み、手元で確かめたスコアとリクエストの大きさを付けて並べる。
+//! Lambdaman は評価すると `solve` のリクエストになる `<task>.<author>.<method>.raw` も含める。
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use itertools::Itertools;
+use serde::{Serialize, Serializer};
+
+use crate::{score, tasks::split_task};
+
+/// ファイル名から読んだ解の情報
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Name {
+    pub task: String,
+    pub author: String,
+    pub method: Option<String>,
+}
+
+/// `lambdaman1.toga.beam.out` を `lambdaman1` `toga` `beam` に分ける（解のファイルでなければ `None`）
+pub fn parse_name(file_name: &str) -> Option<Name> {
+    let (stem, ext) = file_name.rsplit_once('.')?;
+    let (task, rest) = stem.split_once('.')?;
+    let (family, _) = split_task(task)?;
+    let is_solution = ext == "out" || family == "lambdaman" && ext == "raw";
+    if !is_solution || rest.is_empty() {
+        return None;
+    }
+    let (author, method) = match rest.split_once('.') {
+        Some((author, method)) => (author, Some(method.to_owned())),
+        None => (rest, None),
+    };
+    Some(Name {
+        task: task.to_owned(),
+        author: author.to_owned(),
+        method,
+    })
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Entry {
+    pub path: PathBuf,
+    pub family: String,
+    pub number: u32,
+    pub author: String,
+    pub method: Option<String>,
+    /// 手元で数えたスコア（数えられないか間違っていれば `None`）
+    pub score: Option<u64>,
+    /// 送るリクエスト（エンコード済み、作れなければ `None`）
+    pub request: Option<String>,
+    /// 手元のシミュレータで正しいと確かめたか
+    pub verified: bool,
+    /// 確かめて間違っていたときの理由
+    pub error: Option<String>,
+}
+
+impl Entry {
+    /// 解のファイルを確かめて一覧の 1 行にする
+    pub fn load(data: &Path, path: &Path) -> anyhow::Result<Entry> {
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+        let name = parse_name(&file_name)
+            .with_context(|| format!("Not a solution file: {}", path.display()))?;
+        let (family, number) = split_task(&name.task).context("Invalid task")?;
+        // 圧縮したリクエストは重いので 1 度だけ作り、確かめるのにも送るのにも使う
+        let request = score::file_request(&name.task, path);
+        let (score, error) = match &request {
+            Ok(request) => match score::request_score(data, &name.task, path, request) {
+                Ok(score) => (score, None),
+                Err(err) => (None, Some(format!("{err:#}"))),
+            },
+            Err(err) => (None, Some(format!("{err:#}"))),
+        };
+        Ok(Entry {
+            path: path.to_owned(),
+            family: family.to_owned(),
+            number,
+            author: name.author,
+            method: name.method,
+            verified: score.is_some(),
+            score,
+            request: request.ok(),
+            error,
+        })
+    }
+
+    pub fn task(&self) -> String {
+        format!("{}{}", self.family, self.number)
+    }
+
+    /// 送るリクエストのバイト数
+    pub fn size(&self) -> Option<u64> {
+        self.request.as_ref().map(|request| request.len() as u64)
+    }
+
+    /// 送る答え（`.raw` のプログラムは評価して `solve <task> ` を除く）
+    pub fn answer(&self) -> anyhow::Result<String> {
+        let text = fs::read_to_string(&self.path)?;
+        if self.path.extension().is_some_and(|ext| ext == "raw") {
+            let message = crate::eval_encoded(text.trim())?;
+            let prefix = format!("solve {} ", self.task());
+            let answer = message
+                .strip_prefix(&prefix)
+                .with_context(|| format!("Expected '{prefix}...'"))?;
+            return Ok(answer.to_owned());
+        }
+        Ok(text.trim().to_owned())
+    }
+}
+
+/// 書き出す 1 件（リクエストは大きいので大きさだけ）
+#[derive(Serialize)]
+struct ExportedEntry<'a> {
+    path: &'a Path,
+    task: String,
+    family: &'a str,
+    number: u32,
+    author: &'a str,
+    method: Option<&'a str>,
+    score: Option<u64>,
+    size: Option<u64>,
+    verified: bool,
+    error: Option<&'a str>,
+}
+
+impl Serialize for Entry {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        ExportedEntry {
+            path: &self.path,
+            task: self.task(),
+            family: &self.family,
+            number: self.number,
+            author: &self.author,
+            method: self.method.as_deref(),
+            score: self.score,
+            size: self.size(),
+            verified: self.verified,
+            error: self.error.as_deref(),
+        }
+        .serialize(serializer)
+    }
+}
+
+/// 一覧の絞り込み（`None` はどれでもよい）
+#[derive(Clone, Debug, Default)]
+pub struct Query {
+    pub family: Option<String>,
+    pub task: Option<String>,
+    pub author: Option<String>,
+    pub method: Option<String>,
+    /// 確かめたものだけ
+    pub verified: bool,
+}
+
+impl Query {
+    /// ファイル名だけでわかる条件を満たすか
+    pub fn matches_name(&self, name: &Name) -> bool {
+        let eq = |want: &Option<String>, have: &str| want.as_ref().is_none_or(|w| w == have);
+        eq(
+            &self.family,
+            split_task(&name.task).map_or("", |(family, _)| family),
+        ) && eq(&self.task, &name.task)
+            && eq(&self.author, &name.author)
+            && eq(&self.method, name.method.as_deref().unwrap_or(""))
+    }
+
+    pub fn matches(&self, entry: &Entry) -> bool {
+        let name = Name {
+            task: entry.task(),
+            author: entry.author.clone(),
+            method: entry.method.clone(),
+        };
+        self.matches_name(&name) && (!self.verified || entry.verified)
+    }
+}
+
+/// 種類と番号の順に並べた解の一覧（JSON では配列）
+#[derive(Clone, Debug, Default, Serialize)]
+#[serde(transparent)]
+pub struct Catalog {
+    pub entries: Vec<Entry>,
+}
+
+impl Catalog {
+    /// `data/<family>/` の解のうち `query` に合うものを読んで確かめる
+    ///
+    /// 確かめるのは重いので、ファイル名で絞れるものは先に絞る。
+    pub fn scan(data: &Path, query: &Query) -> anyhow::Result<Catalog> {
+        let mut entries = vec![];
+        for dir in fs::read_dir(data)? {
+            let dir = dir?.path();
+            if !dir.is_dir() {
+                continue;
+            }
+            for file in fs::read_dir(&dir)? {
+                let path = file?.path();
+                let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+                if !parse_name(&file_name).is_some_and(|name| query.matches_name(&name)) {
+                    continue;
+                }
+                let entry = Entry::load(data, &path)?;
+                if query.matches(&entry) {
+                    entries.push(entry);
+                }
+            }
+        }
+        entries.sort_by(|a, b| (&a.family, a.number, &a.path).cmp(&(&b.family, b.number, &b.path)));
+        Ok(Catalog { entries })
+    }
+
+    pub fn query<'a>(&'a self, query: &'a Query) -> impl Iterator<Item = &'a Entry> + 'a {
+        self.entries.iter().filter(|entry| query.matches(entry))
+    }
+
+    /// 確かめた解のうちスコアが一番小さいもの（同じなら先に並ぶもの）
+    pub fn best(&self, task: &str) -> Option<&Entry> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.verified && entry.task() == task)
+            .min_by_key(|entry| entry.score)
+    }
+
+    /// 解のある課題を並び順に
+    pub fn tasks(&self) -> Vec<String> {
+        self.entries.iter().map(Entry::task).dedup().collect()
+    }
+}
@@ -0,0 +1,296 @@
+//! communicate エンドポイントのクライアント
+//!
+//! 1 分あたり 20 リクエストの制限をトークンバケットで守り、
+//! 空の応答 (制限に引っかかったとき) や 5xx は間隔を倍にしながら再送する。
+//! 通信は `log::RequestLog` に残し、リプレイではサーバーの代わりにそこから答える。
+
+pub mod log;
+
+use std::{
+    env,
+    path::PathBuf,
+    sync::{Arc, OnceLock},
+    time::Duration,
+};
+
+use anyhow::{anyhow, bail, Context};
+use dotenv::dotenv;
+use reqwest::StatusCode;
+use tokio::{sync::Mutex, time::Instant};
+
+use crate::token::{self, Token};
+
+use self::log::{RequestLog, DEFAULT_LOG};
+
+pub const ENDPOINT: &str = "https://boundvariable.space/communicate";
+
+/// 1 分あたりのリクエスト数の上限
+pub const REQUESTS_PER_MINUTE: u32 = 20;
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// トークンバケットによるレート制限
+///
+/// 最大 `capacity` 個まで溜まり、`interval` ごとに 1 個増える。
+pub struct RateLimiter {
+    capacity: u32,
+    interval: Duration,
+    bucket: Mutex<Bucket>,
+}
+
+impl RateLimiter {
+    pub fn new(capacity: u32, interval: Duration) -> Self {
+        RateLimiter {
+            capacity,
+            interval,
+            bucket: Mutex::new(Bucket {
+                tokens: capacity as f64,
+                updated: Instant::now(),
+            }),
+        }
+    }
+
+    /// 1 分あたり `n` 回まで
+    pub fn per_minute(n: u32) -> Self {
+        RateLimiter::new(n, Duration::from_secs(60) / n)
+    }
+
+    /// トークンを 1 個取り出す（なければ溜まるまで待つ）
+    pub async fn acquire(&self) {
+        let mut bucket = self.bucket.lock().await;
+        loop {
+            let now = Instant::now();
+            let refilled =
+                now.duration_since(bucket.updated).as_secs_f64() / self.interval.as_secs_f64();
+            bucket.tokens = (bucket.tokens + refilled).min(self.capacity as f64);
+            bucket.updated = now;
+            if bucket.tokens >= 1.0 {
+                bucket.tokens -= 1.0;
+                return;
+            }
+            // ロックを持ったまま待って順番を守る
+            tokio::time::sleep(self.interval.mul_f64(1.0 - bucket.tokens)).await;
+        }
+    }
+}
+
+/// プロセス内で共有する既定のレート制限
+fn shared_limiter() -> Arc<RateLimiter> {
+    static LIMITER: OnceLock<Arc<RateLimiter>> = OnceLock::new();
+    LIMITER
+        .get_or_init(|| Arc::new(RateLimiter::per_minute(REQUESTS_PER_MINUTE)))
+        .clone()
+}
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub url: String,
+    pub token: String,
+    /// 再送する回数の上限
+    pub retries: u32,
+    /// 最初の再送までの待ち時間（1 回ごとに倍にする）
+    pub backoff: Duration,
+    /// 1 回のリクエストのタイムアウト
+    pub timeout: Duration,
+    /// 通信を追記する JSONL
+    pub log: Option<PathBuf>,
+    /// `log` から答えてサーバーには送らない
+    pub replay: bool,
+}
+
+impl Config {
+    pub fn new(url: &str, token: &str) -> Self {
+        Config {
+            url: url.to_owned(),
+            token: token.to_owned(),
+            retries: 5,
+            backoff: Duration::from_secs(3),
+            timeout: Duration::from_secs(60),
+            log: None,
+            replay: false,
+        }
+    }
+
+    /// `.env` か環境変数の `TOKEN` と `ICFPC_URL` (省略時は `ENDPOINT`) を使い、
+    /// `log::DEFAULT_LOG` に記録する
+    pub fn from_env() -> anyhow::Result<Self> {
+        ClientArgs::default().config()
+    }
+}
+
+/// コマンドラインから接続先を上書きする
+#[derive(clap::Args, Clone, Debug, Default)]
+#[command(about = None, long_about = None)]
+pub struct ClientArgs {
+    /// URL of the communicate endpoint (defaults to $ICFPC_URL or the official server)
+    /// e.g. http://localhost:8000/communicate
+    #[arg(long, global = true)]
+    pub url: Option<String>,
+    /// Bearer token (defaults to $TOKEN)
+    #[arg(long, global = true)]
+    pub token: Option<String>,
+    /// JSONL file every request and response is appended to
+    /// e.g. data/requests.jsonl
+    #[arg(long, global = true, default_value = DEFAULT_LOG)]
+    pub log: Option<PathBuf>,
+    /// Do not record requests
+    #[arg(long, global = true, conflicts_with = "replay")]
+    pub no_log: bool,
+    /// Answer requests from the log instead of contacting the server
+    #[arg(long, global = true)]
+    pub replay: bool,
+}
+
+impl ClientArgs {
+    pub fn config(&self) -> anyhow::Result<Config> {
+        dotenv().ok();
+        let url = match &self.url {
+            Some(url) => url.clone(),
+            None => env::var("ICFPC_URL").unwrap_or_else(|_| ENDPOINT.to_owned()),
+        };
+        let token = match &self.token {
+            Some(token) => token.clone(),
+            // リプレイでは送らないのでなくてもよい
+            None if self.replay => env::var("TOKEN").unwrap_or_default(),
+            None => env::var("TOKEN").context("TOKEN is not set: pass --token or set $TOKEN")?,
+        };
+        let log = match &self.log {
+            _ if self.no_log => None,
+            Some(log) => Some(log.clone()),
+            None => Some(PathBuf::from(DEFAULT_LOG)),
+        };
+        Ok(Config {
+            log,
+            replay: self.replay,
+            ..Config::new(&url, &token)
+        })
+    }
+
+    pub fn client(&self) -> anyhow::Result<Client> {
+        Client::new(self.config()?)
+    }
+}
+
+/// 1 回送った結果（`Retry` は再送すれば成功するかもしれない失敗）
+enum Attempt {
+    Done(String),
+    Retry(anyhow::Error),
+}
+
+/// 複製してもレート制限は共有される
+#[derive(Clone)]
+pub struct Client {
+    http: reqwest::Client,
+    config: Config,
+    limiter: Arc<RateLimiter>,
+    log: Option<Arc<RequestLog>>,
+}
+
+impl Client {
+    pub fn new(config: Config) -> anyhow::Result<Self> {
+        let log = match &config.log {
+            Some(path) if config.replay => Some(Arc::new(RequestLog::replay(path)?)),
+            Some(path) => Some(Arc::new(RequestLog::record(path))),
+            None if config.replay => bail!("Replay needs a log file"),
+            None => None,
+        };
+        Ok(Client {
+            http: reqwest::Client::builder().timeout(config.timeout).build()?,
+            config,
+            limiter: shared_limiter(),
+            log,
+        })
+    }
+
+    pub fn from_env() -> anyhow::Result<Self> {
+        Client::new(Config::from_env()?)
+    }
+
+    /// 既定の共有のものの代わりに `limiter` を使う
+    pub fn with_limiter(mut self, limiter: Arc<RateLimiter>) -> Self {
+        self.limiter = limiter;
+        self
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// エンコード済みのリクエストを送り、応答の本文をそのまま返す
+    pub async fn send_raw(&self, encoded: &str) -> anyhow::Result<String> {
+        if let Some(log) = self.log.as_ref().filter(|log| log.is_replay()) {
+            return log
+                .lookup(encoded)
+                .map(str::to_owned)
+                .with_context(|| format!("No response for {encoded} in {}", log.path().display()));
+        }
+        let mut backoff = self.config.backoff;
+        let mut retries = 0;
+        loop {
+            self.limiter.acquire().await;
+            let start = Instant::now();
+            let error = match self.post(encoded).await? {
+                Attempt::Done(body) => {
+                    if let Some(log) = &self.log {
+                        log.append(encoded, &body, start.elapsed())?;
+                    }
+                    return Ok(body);
+                }
+                Attempt::Retry(error) => error,
+            };
+            if retries == self.config.retries {
+                return Err(error.context(format!("Gave up after {retries} retries")));
+            }
+            tokio::time::sleep(backoff).await;
+            backoff *= 2;
+            retries += 1;
+        }
+    }
+
+    /// 1 回送る（4xx は再送しても無駄なのでエラーにする）
+    async fn post(&self, encoded: &str) -> anyhow::Result<Attempt> {
+        let response = match self
+            .http
+            .post(&self.config.url)
+            .bearer_auth(&self.config.token)
+            .body(encoded.to_owned())
+            .send()
+            .await
+        {
+            Ok(response) => response,
+            // 接続できない・タイムアウト
+            Err(err) => return Ok(Attempt::Retry(err.into())),
+        };
+        let status = response.status();
+        if status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS {
+            return Ok(Attempt::Retry(anyhow!("Server responded {status}")));
+        }
+        if !status.is_success() {
+            bail!("Request rejected: {status}");
+        }
+        let body = match response.text().await {
+            Ok(body) => body,
+            Err(err) => return Ok(Attempt::Retry(err.into())),
+        };
+        if body.is_empty() {
+            return Ok(Attempt::Retry(anyhow!(
+                "Empty response: you might have reached the rate limit of {REQUESTS_PER_MINUTE} requests per minute"
+            )));
+        }
+        Ok(Attempt::Done(body))
+    }
+
+    /// エンコード済みのリクエストを送り、応答をトークン列にする
+    pub async fn send(&self, encoded: &str) -> anyhow::Result<Vec<Token>> {
+        token::decode_token_stream(&self.send_raw(encoded).await?)
+    }
+
+    /// 文字列を送って評価した応答を返す（応答は深いことがあるので大きなスタックで評価する）
+    pub async fn request(&self, message: &str) -> anyhow::Result<String> {
+        let request = token::encode(&token::encode_string(message)?)?;
+        crate::eval_encoded(&self.send_raw(&request).await?)
+    }
+}
@@ -0,0 +1,144 @@
+//! 送ったリクエストと応答を JSONL に追記し、同じリクエストにはログから答える
+//!
+//! 1 行が 1 回の通信で、`timestamp` `request` `decoded_request` `response`
+//! `decoded_response` `latency_ms` を持つ。
+
+use std::{
+    collections::HashMap,
+    fs::{self, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use crate::token::{self, Token};
+
+/// 既定のログの置き場所
+pub const DEFAULT_LOG: &str = "data/requests.jsonl";
+
+/// ログの 1 行
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LogEntry {
+    pub timestamp: String,
+    pub request: String,
+    pub decoded_request: Option<String>,
+    pub response: String,
+    pub decoded_response: Option<String>,
+    pub latency_ms: u64,
+}
+
+pub struct RequestLog {
+    path: PathBuf,
+    /// `Some` ならサーバーに送らずログから答える（リクエスト → 最後の応答）
+    replay: Option<HashMap<String, String>>,
+    file: Mutex<()>,
+}
+
+impl RequestLog {
+    /// `path` に追記する
+    pub fn record(path: impl Into<PathBuf>) -> Self {
+        RequestLog {
+            path: path.into(),
+            replay: None,
+            file: Mutex::new(()),
+        }
+    }
+
+    /// `path` のログを読み込んで、同じリクエストにはそこから答える
+    pub fn replay(path: impl Into<PathBuf>) -> anyhow::Result<Self> {
+        let path = path.into();
+        let mut responses = HashMap::new();
+        let text = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        for (i, line) in text.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let entry = serde_json::from_str::<LogEntry>(line)
+                .with_context(|| format!("{}:{}", path.display(), i + 1))?;
+            responses.insert(entry.request, entry.response);
+        }
+        Ok(RequestLog {
+            path,
+            replay: Some(responses),
+            file: Mutex::new(()),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn is_replay(&self) -> bool {
+        self.replay.is_some()
+    }
+
+    /// ログに残っている応答（リプレイでなければ常に `None`）
+    pub fn lookup(&self, encoded: &str) -> Option<&str> {
+        self.replay.as_ref()?.get(encoded).map(String::as_str)
+    }
+
+    /// 1 回分を追記する（リプレイ中は何もしない）
+    pub fn append(&self, encoded: &str, response: &str, latency: Duration) -> anyhow::Result<()> {
+        if self.is_replay() {
+            return Ok(());
+        }
+        let entry = LogEntry {
+            timestamp: timestamp(SystemTime::now()),
+            request: encoded.to_owned(),
+            decoded_request: decode_string(encoded),
+            response: response.to_owned(),
+            decoded_response: decode_string(response),
+            latency_ms: latency.as_millis() as u64,
+        };
+        let _lock = self.file.lock().unwrap();
+        if let Some(dir) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .with_context(|| format!("Failed to open {}", self.path.display()))?;
+        writeln!(file, "{}", serde_json::to_string(&entry)?)?;
+        Ok(())
+    }
+}
+
+/// 文字列 1 個のときだけ読める形にする
+///
+/// それ以外は評価しない（圧縮したプログラムや efficiency の課題は評価すると重いか、深くてスタックが溢れる）。
+fn decode_string(encoded: &str) -> Option<String> {
+    match token::decode_token_stream(encoded).ok()?.as_slice() {
+        [Token::String(s)] => Some(s.clone()),
+        _ => None,
+    }
+}
+
+/// UTC の RFC 3339 形式 `2024-06-29T12:34:56.789Z`
+pub fn timestamp(time: SystemTime) -> String {
+    let since = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since.as_secs() as i64;
+    let (days, rem) = (secs.div_euclid(86400), secs.rem_euclid(86400));
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}.{:03}Z",
+        rem / 3600,
+        rem / 60 % 60,
+        rem % 60,
+        since.subsec_millis()
+    )
+}
@@ -0,0 +1,229 @@
+//! 小さな関数型言語を ICFP のトークン列にコンパイルする
+//!
+//! ```text
+//! # 階乗
+//! let rec fact n = if n == 0 then 1 else n * fact (n - 1) in
+//! let greet name = "Hello, " ++ name ++ "!" in
+//! greet (int_to_str (fact 10))
+//! ```
+//!
+//! - `let x = e in body`、`let f x y = e in body`、`let rec f x = e in body`
+//! - `fn x y -> e` (`\x y -> e` とも書ける)、`if c then a else b`、`f x y` で関数適用
+//! - 中置演算子は優先順位の低い順に `||`、`&&`、`== != < > <= >=`、`+ - ++`、`* / %`
+//! - 前置演算子 `-` `!`、組み込み関数 `take` `drop` `str_to_int` `int_to_str`
+//! - 整数・文字列 (`\"` `\\` `\n` でエスケープする)・`true` `false` のリテラル、`#` から行末はコメント
+//!
+//! `let rec` は Y コンビネータで書き直し、変数はスコープ内で重ならない最も短い名前にする。
+
+use std::fmt;
+
+use anyhow::bail;
+use num_bigint::BigInt;
+
+use crate::{
+    ast::{stdlib, Expr},
+    token::{BinaryOp, Token, UnaryOp},
+};
+
+mod parser;
+
+pub use parser::parse;
+
+/// 1 始まりの行・列
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Pos {
+    pub line: usize,
+    pub col: usize,
+}
+
+impl fmt::Display for Pos {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.col)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Term {
+    Int(BigInt),
+    Str(String),
+    Bool(bool),
+    Var(String, Pos),
+    Lambda(Vec<String>, Box<Term>),
+    App(Box<Term>, Vec<Term>),
+    Let {
+        name: String,
+        rec: bool,
+        value: Box<Term>,
+        body: Box<Term>,
+    },
+    If(Box<Term>, Box<Term>, Box<Term>),
+    Unary(UnaryOp, Box<Term>),
+    Binary(BinaryOp, Box<Term>, Box<Term>),
+}
+
+enum Builtin {
+    Unary(UnaryOp),
+    Binary(BinaryOp),
+}
+
+fn builtin(name: &str) -> Option<Builtin> {
+    Some(match name {
+        "take" => Builtin::Binary(BinaryOp::Take),
+        "drop" => Builtin::Binary(BinaryOp::Drop),
+        "str_to_int" => Builtin::Unary(UnaryOp::ToInt),
+        "int_to_str" => Builtin::Unary(UnaryOp::ToString),
+        _ => return None,
+    })
+}
+
+fn apply(f: Expr, x: Expr) -> Expr {
+    Expr::BinaryOp(BinaryOp::Apply, f.into(), x.into())
+}
+
+#[derive(Default)]
+struct Codegen {
+    /// 束縛されている名前と変数の番号（後ろほど内側）
+    scope: Vec<(String, BigInt)>,
+}
+
+impl Codegen {
+    fn lookup(&self, name: &str) -> Option<&BigInt> {
+        self.scope
+            .iter()
+            .rev()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v)
+    }
+
+    /// `name` で隠れる変数を除き、見えている変数と重ならない最小の番号
+    fn fresh(&self, name: &str) -> BigInt {
+        let mut visible = vec![];
+        for (i, (n, v)) in self.scope.iter().enumerate() {
+            if n != name && !self.scope[i + 1..].iter().any(|(m, _)| m == n) {
+                visible.push(v);
+            }
+        }
+        let mut v = BigInt::from(1);
+        while visible.contains(&&v) {
+            v += 1;
+        }
+        v
+    }
+
+    /// `name` を束縛して `body` をコンパイルし、ラムダ式にする
+    fn lambda(
+        &mut self,
+        name: &str,
+        body: impl FnOnce(&mut Self) -> anyhow::Result<Expr>,
+    ) -> anyhow::Result<Expr> {
+        let v = self.fresh(name);
+        self.scope.push((name.to_owned(), v.clone()));
+        let body = body(self);
+        self.scope.pop();
+        Ok(Expr::Lambda(v, body?.into()))
+    }
+
+    fn lambdas(&mut self, params: &[String], body: &Term) -> anyhow::Result<Expr> {
+        match params {
+            [] => self.gen(body),
+            [param, rest @ ..] => self.lambda(param, |g| g.lambdas(rest, body)),
+        }
+    }
+
+    /// 組み込み関数を引数に適用する（足りない引数はラムダ式で受け取る）
+    fn builtin(&mut self, name: &str, b: Builtin, args: &[Term]) -> anyhow::Result<Expr> {
+        let arity = match b {
+            Builtin::Unary(_) => 1,
+            Builtin::Binary(_) => 2,
+        };
+        if args.len() < arity {
+            // 利用者の識別子と重ならない名前で η 展開する
+            let params = (args.len()..arity)
+                .map(|i| format!("${i}"))
+                .collect::<Vec<_>>();
+            let pos = Pos { line: 0, col: 0 };
+            let mut args = args.to_vec();
+            args.extend(params.iter().map(|p| Term::Var(p.clone(), pos)));
+            let f = Term::Var(name.to_owned(), pos);
+            return self.gen(&Term::Lambda(
+                params,
+                Box::new(Term::App(Box::new(f), args)),
+            ));
+        }
+        let mut operands = args[..arity]
+            .iter()
+            .map(|a| self.gen(a))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let mut expr = match b {
+            Builtin::Unary(o) => Expr::UnaryOp(o, operands.remove(0).into()),
+            Builtin::Binary(o) => {
+                let r = operands.pop().expect("unreachable");
+                let l = operands.pop().expect("unreachable");
+                Expr::BinaryOp(o, l.into(), r.into())
+            }
+        };
+        for arg in &args[arity..] {
+            expr = apply(expr, self.gen(arg)?);
+        }
+        Ok(expr)
+    }
+
+    fn gen(&mut self, term: &Term) -> anyhow::Result<Expr> {
+        Ok(match term {
+            Term::Int(i) => i.clone().into(),
+            Term::Str(s) => s.clone().into(),
+            Term::Bool(b) => (*b).into(),
+            Term::Var(name, pos) => match (self.lookup(name), builtin(name)) {
+                (Some(v), _) => Expr::Var(v.clone()),
+                (None, Some(b)) => self.builtin(name, b, &[])?,
+                (None, None) => bail!("Unbound variable {name} at {pos}"),
+            },
+            Term::Lambda(params, body) => self.lambdas(params, body)?,
+            Term::App(f, args) => {
+                if let Term::Var(name, _) = &**f {
+                    if let (None, Some(b)) = (self.lookup(name), builtin(name)) {
+                        return self.builtin(name, b, args);
+                    }
+                }
+                let mut expr = self.gen(f)?;
+                for arg in args {
+                    expr = apply(expr, self.gen(arg)?);
+                }
+                expr
+            }
+            Term::Let {
+                name,
+                rec,
+                value,
+                body,
+            } => {
+                let value = if *rec {
+                    // let rec f = e in body => (λf. body) (Y (λf. e))
+                    apply(stdlib::y(), self.lambda(name, |g| g.gen(value))?)
+                } else {
+                    self.gen(value)?
+                };
+                apply(self.lambda(name, |g| g.gen(body))?, value)
+            }
+            Term::If(c, t, e) => Expr::If(
+                self.gen(c)?.into(),
+                self.gen(t)?.into(),
+                self.gen(e)?.into(),
+            ),
+            Term::Unary(o, e) => Expr::UnaryOp(o.clone(), self.gen(e)?.into()),
+            Term::Binary(o, l, r) => {
+                Expr::BinaryOp(o.clone(), self.gen(l)?.into(), self.gen(r)?.into())
+            }
+        })
+    }
+}
+
+/// 構文木を `Expr` にする
+pub fn lower(term: &Term) -> anyhow::Result<Expr> {
+    Codegen::default().gen(term)
+}
+
+/// ソースコードをトークン列にコンパイルする
+pub fn compile(src: &str) -> anyhow::Result<Vec<Token>> {
+    Ok(lower(&parse(src)?)?.to_tokens())
+}
@@ -0,0 +1,314 @@
+use anyhow::{bail, Context};
+use num_bigint::BigInt;
+
+use super::{Pos, Term};
+use crate::token::{BinaryOp, UnaryOp};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Lexeme {
+    Int(BigInt),
+    Str(String),
+    Ident(String),
+    Symbol(&'static str),
+}
+
+/// 長いものから順に並べる
+const SYMBOLS: [&str; 20] = [
+    "->", "==", "!=", "<=", ">=", "++", "&&", "||", "(", ")", "=", "<", ">", "+", "-", "*", "/",
+    "%", "!", "\\",
+];
+
+const KEYWORDS: [&str; 9] = [
+    "let", "rec", "in", "fn", "if", "then", "else", "true", "false",
+];
+
+fn is_ident_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '\''
+}
+
+/// 字句に分ける（`#` から行末まではコメント）
+fn lex(src: &str) -> anyhow::Result<Vec<(Pos, Lexeme)>> {
+    let mut lexemes = vec![];
+    for (i, line) in src.lines().enumerate() {
+        let chars = line.chars().collect::<Vec<_>>();
+        let mut j = 0;
+        while j < chars.len() {
+            let pos = Pos {
+                line: i + 1,
+                col: j + 1,
+            };
+            let c = chars[j];
+            if c.is_whitespace() {
+                j += 1;
+            } else if c == '#' {
+                break;
+            } else if c.is_ascii_digit() {
+                let len = chars[j..].iter().take_while(|c| c.is_ascii_digit()).count();
+                let digits = chars[j..j + len].iter().collect::<String>();
+                lexemes.push((pos, Lexeme::Int(digits.parse()?)));
+                j += len;
+            } else if c.is_ascii_alphabetic() || c == '_' {
+                let len = chars[j..].iter().take_while(|&&c| is_ident_char(c)).count();
+                let ident = chars[j..j + len].iter().collect::<String>();
+                lexemes.push((pos, Lexeme::Ident(ident)));
+                j += len;
+            } else if c == '"' {
+                let mut s = String::new();
+                j += 1;
+                loop {
+                    match chars.get(j) {
+                        None => bail!("Unterminated string at {pos}"),
+                        Some('"') => break,
+                        Some('\\') => {
+                            match chars.get(j + 1) {
+                                Some('n') => s.push('\n'),
+                                Some(&c @ ('"' | '\\')) => s.push(c),
+                                _ => bail!("Unknown escape at {}:{}", i + 1, j + 1),
+                            }
+                            j += 2;
+                        }
+                        Some(&c) => {
+                            s.push(c);
+                            j += 1;
+                        }
+                    }
+                }
+                j += 1;
+                lexemes.push((pos, Lexeme::Str(s)));
+            } else {
+                let rest = chars[j..].iter().collect::<String>();
+                let symbol = SYMBOLS
+                    .iter()
+                    .find(|s| rest.starts_with(*s))
+                    .with_context(|| format!("Unexpected character at {pos}: {c}"))?;
+                lexemes.push((pos, Lexeme::Symbol(symbol)));
+                j += symbol.len();
+            }
+        }
+    }
+    Ok(lexemes)
+}
+
+struct Parser {
+    lexemes: Vec<(Pos, Lexeme)>,
+    pos: usize,
+    end: Pos,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Lexeme> {
+        self.lexemes.get(self.pos).map(|(_, l)| l)
+    }
+
+    /// 次の字句の位置（末尾なら最後の行の次）
+    fn here(&self) -> Pos {
+        self.lexemes.get(self.pos).map_or(self.end, |&(pos, _)| pos)
+    }
+
+    fn is_symbol(&self, symbol: &str) -> bool {
+        matches!(self.peek(), Some(Lexeme::Symbol(s)) if *s == symbol)
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Lexeme::Ident(s)) if s == keyword)
+    }
+
+    fn expect_symbol(&mut self, symbol: &str) -> anyhow::Result<()> {
+        if !self.is_symbol(symbol) {
+            bail!("Expected '{symbol}' at {}", self.here());
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> anyhow::Result<()> {
+        if !self.is_keyword(keyword) {
+            bail!("Expected '{keyword}' at {}", self.here());
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn ident(&mut self) -> anyhow::Result<String> {
+        match self.peek() {
+            Some(Lexeme::Ident(s)) if !KEYWORDS.contains(&s.as_str()) => {
+                let s = s.clone();
+                self.pos += 1;
+                Ok(s)
+            }
+            _ => bail!("Expected identifier at {}", self.here()),
+        }
+    }
+
+    /// `->` や `=` の前までの引数名
+    fn params(&mut self) -> anyhow::Result<Vec<String>> {
+        let mut params = vec![];
+        while matches!(self.peek(), Some(Lexeme::Ident(s)) if !KEYWORDS.contains(&s.as_str())) {
+            params.push(self.ident()?);
+        }
+        Ok(params)
+    }
+
+    fn expr(&mut self) -> anyhow::Result<Term> {
+        if self.is_keyword("let") {
+            self.pos += 1;
+            let rec = self.is_keyword("rec");
+            if rec {
+                self.pos += 1;
+            }
+            let name = self.ident()?;
+            let params = self.params()?;
+            self.expect_symbol("=")?;
+            let mut value = self.expr()?;
+            if !params.is_empty() {
+                value = Term::Lambda(params, Box::new(value));
+            }
+            self.expect_keyword("in")?;
+            let body = self.expr()?;
+            return Ok(Term::Let {
+                name,
+                rec,
+                value: Box::new(value),
+                body: Box::new(body),
+            });
+        }
+        if self.is_keyword("fn") || self.is_symbol("\\") {
+            self.pos += 1;
+            let pos = self.here();
+            let params = self.params()?;
+            if params.is_empty() {
+                bail!("Expected parameters at {pos}");
+            }
+            self.expect_symbol("->")?;
+            let body = self.expr()?;
+            return Ok(Term::Lambda(params, Box::new(body)));
+        }
+        if self.is_keyword("if") {
+            self.pos += 1;
+            let cond = self.expr()?;
+            self.expect_keyword("then")?;
+            let then = self.expr()?;
+            self.expect_keyword("else")?;
+            let otherwise = self.expr()?;
+            return Ok(Term::If(
+                Box::new(cond),
+                Box::new(then),
+                Box::new(otherwise),
+            ));
+        }
+        self.binary(0)
+    }
+
+    /// 優先順位の低い順に `||` `&&` 比較 `+ - ++` `* / %`
+    fn binary(&mut self, level: usize) -> anyhow::Result<Term> {
+        const LEVELS: [&[&str]; 5] = [
+            &["||"],
+            &["&&"],
+            &["==", "!=", "<", ">", "<=", ">="],
+            &["+", "-", "++"],
+            &["*", "/", "%"],
+        ];
+        if level == LEVELS.len() {
+            return self.unary();
+        }
+        let mut lhs = self.binary(level + 1)?;
+        while let Some(&op) = LEVELS[level].iter().find(|op| self.is_symbol(op)) {
+            self.pos += 1;
+            let rhs = self.binary(level + 1)?;
+            let binary = |o: BinaryOp, lhs, rhs| Term::Binary(o, Box::new(lhs), Box::new(rhs));
+            let not = |t| Term::Unary(UnaryOp::Not, Box::new(t));
+            lhs = match op {
+                "||" => binary(BinaryOp::Or, lhs, rhs),
+                "&&" => binary(BinaryOp::And, lhs, rhs),
+                "==" => binary(BinaryOp::Equal, lhs, rhs),
+                "!=" => not(binary(BinaryOp::Equal, lhs, rhs)),
+                "<" => binary(BinaryOp::Less, lhs, rhs),
+                ">" => binary(BinaryOp::Greater, lhs, rhs),
+                "<=" => not(binary(BinaryOp::Greater, lhs, rhs)),
+                ">=" => not(binary(BinaryOp::Less, lhs, rhs)),
+                "+" => binary(BinaryOp::Add, lhs, rhs),
+                "-" => binary(BinaryOp::Sub, lhs, rhs),
+                "++" => binary(BinaryOp::Concat, lhs, rhs),
+                "*" => binary(BinaryOp::Mul, lhs, rhs),
+                "/" => binary(BinaryOp::Div, lhs, rhs),
+                "%" => binary(BinaryOp::Mod, lhs, rhs),
+                _ => unreachable!(),
+            };
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> anyhow::Result<Term> {
+        if self.is_symbol("-") || self.is_symbol("!") {
+            let o = if self.is_symbol("-") {
+                UnaryOp::Neg
+            } else {
+                UnaryOp::Not
+            };
+            self.pos += 1;
+            return Ok(Term::Unary(o, Box::new(self.unary()?)));
+        }
+        self.app()
+    }
+
+    fn starts_atom(&self) -> bool {
+        match self.peek() {
+            Some(Lexeme::Int(_) | Lexeme::Str(_)) => true,
+            Some(Lexeme::Ident(s)) => {
+                !KEYWORDS.contains(&s.as_str()) || s == "true" || s == "false"
+            }
+            Some(Lexeme::Symbol(s)) => *s == "(",
+            None => false,
+        }
+    }
+
+    fn app(&mut self) -> anyhow::Result<Term> {
+        let f = self.atom()?;
+        let mut args = vec![];
+        while self.starts_atom() {
+            args.push(self.atom()?);
+        }
+        Ok(if args.is_empty() {
+            f
+        } else {
+            Term::App(Box::new(f), args)
+        })
+    }
+
+    fn atom(&mut self) -> anyhow::Result<Term> {
+        let pos = self.here();
+        let term = match self.peek().cloned() {
+            Some(Lexeme::Int(i)) => Term::Int(i),
+            Some(Lexeme::Str(s)) => Term::Str(s),
+            Some(Lexeme::Ident(s)) if s == "true" => Term::Bool(true),
+            Some(Lexeme::Ident(s)) if s == "false" => Term::Bool(false),
+            Some(Lexeme::Ident(_)) => return Ok(Term::Var(self.ident()?, pos)),
+            Some(Lexeme::Symbol("(")) => {
+                self.pos += 1;
+                let term = self.expr()?;
+                self.expect_symbol(")")?;
+                return Ok(term);
+            }
+            Some(l) => bail!("Unexpected {l:?} at {pos}"),
+            None => bail!("Unexpected end of input at {pos}"),
+        };
+        self.pos += 1;
+        Ok(term)
+    }
+}
+
+pub fn parse(src: &str) -> anyhow::Result<Term> {
+    let mut parser = Parser {
+        lexemes: lex(src)?,
+        pos: 0,
+        end: Pos {
+            line: src.lines().count() + 1,
+            col: 1,
+        },
+    };
+    let term = parser.expr()?;
+    if parser.peek().is_some() {
+        bail!("Unexpected input at {}", parser.here());
+    }
+    Ok(term)
+}
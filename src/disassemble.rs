@@ -0,0 +1,224 @@
+//! ICFP のトークン列をアセンブラ (`assemble`) のソースに戻す
+//!
+//! 整数は 10 進数、文字列は `"..."`、変数は `a` `b` ... のような名前にし、
+//! 前置記法の木の形に合わせて字下げする。
+//! アセンブラが付け直す番号と元の番号が違う変数は `La@3` のように番号を固定するので、
+//! 出力をアセンブルすると元のトークン列と同じになる。
+
+use std::collections::HashSet;
+
+use anyhow::{bail, Context};
+use num_bigint::BigInt;
+
+use crate::token::{self, integers, Token};
+
+/// 1 行に収める文字数（字下げを含む）
+const WIDTH: usize = 80;
+
+enum Node {
+    /// 引数を取らないトークン
+    Leaf(String),
+    /// `U` `B` `?` と引数
+    Op(String, Vec<Node>),
+    Lambda(BigInt, Box<Node>),
+    Var(BigInt),
+}
+
+/// 前置記法のトークン列を木にする
+fn parse<'a>(tokens: &mut impl Iterator<Item = &'a Token>) -> anyhow::Result<Node> {
+    let token = tokens.next().context("Unexpected end of tokens")?;
+    let mut op = |arity: usize| {
+        let op = token::encode(std::slice::from_ref(token))?;
+        let args = (0..arity)
+            .map(|_| parse(tokens))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        anyhow::Ok(Node::Op(op, args))
+    };
+    Ok(match token {
+        Token::Boolean(b) => Node::Leaf(if *b { "T" } else { "F" }.to_owned()),
+        Token::Integer(i) => Node::Leaf(i.to_string()),
+        Token::String(s) => Node::Leaf(quote(s)),
+        Token::UnaryOp(_) => op(1)?,
+        Token::BinaryOp(_) => op(2)?,
+        Token::If => op(3)?,
+        Token::Lambda(v) => Node::Lambda(v.clone(), Box::new(parse(tokens)?)),
+        Token::Variable(v) => Node::Var(v.clone()),
+    })
+}
+
+/// エスケープが要るときだけ `e"..."` にする
+fn quote(s: &str) -> String {
+    let closes = s
+        .chars()
+        .zip(s.chars().skip(1))
+        .any(|(c, next)| c == '"' && next.is_whitespace());
+    if !s.contains('\n') && !closes {
+        return format!("\"{s}\"");
+    }
+    let mut quoted = "e\"".to_owned();
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// 束縛されていない変数の番号を集める
+fn free_numbers(node: &Node, bound: &mut Vec<BigInt>, free: &mut HashSet<BigInt>) {
+    match node {
+        Node::Leaf(_) => {}
+        Node::Op(_, args) => {
+            for arg in args {
+                free_numbers(arg, bound, free);
+            }
+        }
+        Node::Lambda(v, body) => {
+            bound.push(v.clone());
+            free_numbers(body, bound, free);
+            bound.pop();
+        }
+        Node::Var(v) => {
+            if !bound.contains(v) {
+                free.insert(v.clone());
+            }
+        }
+    }
+}
+
+/// 先頭の語と、字下げして続ける引数
+struct Tree {
+    head: String,
+    children: Vec<Tree>,
+}
+
+impl Tree {
+    /// 1 行に書いたときの文字数（`limit` を超えたら打ち切る）
+    fn width(&self, limit: usize) -> Option<usize> {
+        let mut width = self.head.len();
+        for child in &self.children {
+            width += 1 + child.width(limit.checked_sub(width + 1)?)?;
+        }
+        (width <= limit).then_some(width)
+    }
+
+    fn write_flat(&self, out: &mut String) {
+        out.push_str(&self.head);
+        for child in &self.children {
+            out.push(' ');
+            child.write_flat(out);
+        }
+    }
+
+    fn render(&self, indent: usize, out: &mut String) {
+        out.push_str(&" ".repeat(indent));
+        if self.children.is_empty() || self.width(WIDTH.saturating_sub(indent)).is_some() {
+            self.write_flat(out);
+            out.push('\n');
+            return;
+        }
+        out.push_str(&self.head);
+        out.push('\n');
+        for child in &self.children {
+            child.render(indent + 2, out);
+        }
+    }
+}
+
+struct Namer {
+    free: HashSet<BigInt>,
+    /// 束縛されていない変数の名前（変数名と重ならないようにする）
+    free_names: HashSet<String>,
+    /// 外側から順に束縛されている変数の番号と名前
+    scope: Vec<(BigInt, String)>,
+}
+
+impl Namer {
+    /// 深さ `depth` の変数名 `a` ... `z` `a1` ... `z1` `a2` ...
+    fn name(&self, depth: usize) -> String {
+        (0..)
+            .map(|i: usize| {
+                let c = (b'a' + (i % 26) as u8) as char;
+                match i / 26 {
+                    0 => c.to_string(),
+                    n => format!("{c}{n}"),
+                }
+            })
+            .filter(|name| !self.free_names.contains(name))
+            .nth(depth)
+            .expect("unreachable")
+    }
+
+    fn tree(&mut self, node: &Node) -> anyhow::Result<Tree> {
+        Ok(match node {
+            Node::Leaf(word) => Tree {
+                head: word.clone(),
+                children: vec![],
+            },
+            Node::Op(op, args) => Tree {
+                head: op.clone(),
+                children: args
+                    .iter()
+                    .map(|arg| self.tree(arg))
+                    .collect::<anyhow::Result<_>>()?,
+            },
+            Node::Lambda(v, body) => {
+                // アセンブラが選ぶ番号と同じなら固定しない
+                let auto = (1..)
+                    .map(BigInt::from)
+                    .find(|n| !self.free.contains(n) && self.scope.iter().all(|(m, _)| m != n))
+                    .expect("unreachable");
+                let name = self.name(self.scope.len());
+                let head = if *v == auto {
+                    format!("L{name}")
+                } else {
+                    format!("L{name}@{v}")
+                };
+                self.scope.push((v.clone(), name));
+                let body = self.tree(body);
+                self.scope.pop();
+                Tree {
+                    head,
+                    children: vec![body?],
+                }
+            }
+            Node::Var(v) => match self.scope.iter().rev().find(|(m, _)| m == v) {
+                Some((_, name)) => Tree {
+                    head: format!("v{name}"),
+                    children: vec![],
+                },
+                None => Tree {
+                    head: format!("v{}", integers::encode(v.clone())?),
+                    children: vec![],
+                },
+            },
+        })
+    }
+}
+
+/// トークン列をアセンブラのソースにする
+pub fn disassemble(tokens: &[Token]) -> anyhow::Result<String> {
+    let mut iter = tokens.iter();
+    let node = parse(&mut iter)?;
+    if iter.next().is_some() {
+        bail!("Trailing tokens after the program");
+    }
+    let mut free = HashSet::new();
+    free_numbers(&node, &mut vec![], &mut free);
+    let free_names = free
+        .iter()
+        .map(|v| integers::encode(v.clone()))
+        .collect::<anyhow::Result<_>>()?;
+    let mut namer = Namer {
+        free,
+        free_names,
+        scope: vec![],
+    };
+    let mut out = String::new();
+    namer.tree(&node)?.render(0, &mut out);
+    Ok(out)
+}
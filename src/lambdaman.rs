@@ -0,0 +1,151 @@
+use std::str::FromStr;
+
+use anyhow::{bail, Context};
+
+/// 1 回の提出で許される移動回数の上限
+pub const MAX_MOVES: usize = 1_000_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl Direction {
+    /// `U` `D` `L` `R` の順
+    pub const ALL: [Direction; 4] = [
+        Direction::Up,
+        Direction::Down,
+        Direction::Left,
+        Direction::Right,
+    ];
+
+    pub fn from_char(c: char) -> Option<Self> {
+        match c {
+            'U' => Some(Direction::Up),
+            'D' => Some(Direction::Down),
+            'L' => Some(Direction::Left),
+            'R' => Some(Direction::Right),
+            _ => None,
+        }
+    }
+
+    pub fn to_char(self) -> char {
+        match self {
+            Direction::Up => 'U',
+            Direction::Down => 'D',
+            Direction::Left => 'L',
+            Direction::Right => 'R',
+        }
+    }
+
+    fn delta(self) -> (isize, isize) {
+        match self {
+            Direction::Up => (-1, 0),
+            Direction::Down => (1, 0),
+            Direction::Left => (0, -1),
+            Direction::Right => (0, 1),
+        }
+    }
+}
+
+/// `data/lambdaman/*.in` のマップ
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Map {
+    grid: Vec<Vec<u8>>,
+    start: (usize, usize),
+    pills: usize,
+}
+
+impl FromStr for Map {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let grid = s
+            .lines()
+            .map(|line| line.trim_end().as_bytes().to_vec())
+            .filter(|line| !line.is_empty())
+            .collect::<Vec<_>>();
+        let mut start = None;
+        let mut pills = 0;
+        for (i, row) in grid.iter().enumerate() {
+            for (j, &c) in row.iter().enumerate() {
+                match c {
+                    b'L' => start = Some((i, j)),
+                    b'.' => pills += 1,
+                    b'#' => {}
+                    unk => bail!("Unexpected cell: {}", unk as char),
+                }
+            }
+        }
+        let start = start.context("No Lambda-Man in the map")?;
+        Ok(Map { grid, start, pills })
+    }
+}
+
+impl Map {
+    pub fn pills(&self) -> usize {
+        self.pills
+    }
+
+    pub fn walker(&self) -> Walker<'_> {
+        Walker {
+            map: self,
+            pos: self.start,
+            eaten: self.grid.iter().map(|row| vec![false; row.len()]).collect(),
+            remaining: self.pills,
+        }
+    }
+
+    /// 移動列を実行し、食べ残したピルの数を返す
+    pub fn simulate(&self, moves: &str) -> anyhow::Result<usize> {
+        if moves.len() > MAX_MOVES {
+            bail!("Too many moves: {}", moves.len());
+        }
+        let mut walker = self.walker();
+        for c in moves.chars() {
+            walker.step(Direction::from_char(c).context("Unexpected move")?);
+        }
+        Ok(walker.remaining())
+    }
+
+    fn is_open(&self, i: isize, j: isize) -> bool {
+        usize::try_from(i)
+            .ok()
+            .zip(usize::try_from(j).ok())
+            .and_then(|(i, j)| self.grid.get(i)?.get(j))
+            .is_some_and(|&c| c != b'#')
+    }
+}
+
+/// マップ上を 1 手ずつ動かすためのシミュレータ
+#[derive(Clone, Debug)]
+pub struct Walker<'a> {
+    map: &'a Map,
+    pos: (usize, usize),
+    eaten: Vec<Vec<bool>>,
+    remaining: usize,
+}
+
+impl Walker<'_> {
+    /// 壁やマップの外には移動しない
+    pub fn step(&mut self, dir: Direction) {
+        let (di, dj) = dir.delta();
+        let (i, j) = (self.pos.0 as isize + di, self.pos.1 as isize + dj);
+        if !self.map.is_open(i, j) {
+            return;
+        }
+        let (i, j) = (i as usize, j as usize);
+        self.pos = (i, j);
+        if self.map.grid[i][j] == b'.' && !self.eaten[i][j] {
+            self.eaten[i][j] = true;
+            self.remaining -= 1;
+        }
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.remaining
+    }
+}
@@ -1,32 +1,39 @@
-use std::env;
-
-use anyhow::bail;
 use ast::{Expr, Value};
-use dotenv::dotenv;
+use client::Client;
 use token::Token;
 
 pub mod assemble;
 pub mod ast;
+pub mod catalog;
+pub mod client;
+pub mod compile;
+pub mod disassemble;
+pub mod lambdaman;
+pub mod mock_server;
+pub mod score;
+pub mod scoreboard;
+pub mod spaceship;
+pub mod tasks;
+pub mod threed;
 pub mod token;
 
-pub const ENDPOINT: &str = "https://boundvariable.space/communicate";
+pub use client::ENDPOINT;
 
+/// 環境変数の設定で送る（何度も送るなら `client::Client` を作って使い回す）
 pub async fn send(encoded: String) -> anyhow::Result<Vec<Token>> {
-    dotenv().ok();
-    let response = reqwest::Client::new()
-        .post(ENDPOINT)
-        .bearer_auth(env::var("TOKEN")?)
-        .body(encoded)
-        .send()
-        .await?;
-    let result = response.error_for_status()?.text().await?;
-    if result.is_empty() {
-        bail!(
-            "You might have reached the rate limit 20 requests per minute. Please try again later."
-        )
-    }
-    let tokens = token::decode_token_stream(&result)?;
-    Ok(tokens)
+    Client::from_env()?.send(&encoded).await
+}
+
+/// トークン列の文字列をデコードして評価する
+///
+/// 評価器は再帰が深いので 1GB のスタックのスレッドで評価する。
+pub fn eval_encoded(encoded: &str) -> anyhow::Result<String> {
+    let encoded = encoded.to_owned();
+    std::thread::Builder::new()
+        .stack_size(1 << 30)
+        .spawn(move || eval_tokens(&token::decode_token_stream(&encoded)?))?
+        .join()
+        .map_err(|_| anyhow::anyhow!("Evaluator panicked"))?
 }
 
 pub fn eval_tokens(tokens: &[Token]) -> anyhow::Result<String> {
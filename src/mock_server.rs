@@ -0,0 +1,326 @@
+//! オフラインで試すための communicate エンドポイントの代わり
+//!
+//! POST された ICFP のプログラムを自前の評価器で評価し、
+//! `get <task>` には `data/` に保存した本物の応答 (`.raw`) をそのまま、`solve <task> <answer>` には自前のシミュレータで
+//! 判定した結果を返す。1 分あたりのリクエスト数を超えると本物と同じく空の応答を返す。
+
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    fs,
+    net::SocketAddr,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::Context;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+    task::JoinHandle,
+};
+
+use crate::{
+    client::REQUESTS_PER_MINUTE,
+    lambdaman::Map,
+    spaceship::Problem,
+    token::{self, Token},
+};
+
+/// 応答の本文
+enum Reply {
+    /// そのまま返す（保存してある本物の応答）
+    Raw(String),
+    /// 文字列のトークンにして返す
+    Text(String),
+}
+
+pub struct MockServer {
+    data: PathBuf,
+    requests_per_minute: usize,
+    /// 直近 1 分のリクエストの時刻
+    recent: Mutex<VecDeque<Instant>>,
+    /// 課題ごとの一番良いスコア
+    scores: Mutex<HashMap<String, usize>>,
+}
+
+impl MockServer {
+    /// `data` 以下の `{family}/{task}.raw` を課題として配る
+    pub fn new(data: impl Into<PathBuf>) -> Self {
+        MockServer {
+            data: data.into(),
+            requests_per_minute: REQUESTS_PER_MINUTE as usize,
+            recent: Mutex::new(VecDeque::new()),
+            scores: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// 1 分あたり `n` 回まで受け付ける
+    pub fn with_rate_limit(mut self, n: usize) -> Self {
+        self.requests_per_minute = n;
+        self
+    }
+
+    /// `addr` で待ち受けを始める（`127.0.0.1:0` なら空いているポートを使う）
+    pub async fn bind(self, addr: &str) -> anyhow::Result<MockHandle> {
+        let listener = TcpListener::bind(addr).await?;
+        let addr = listener.local_addr()?;
+        let server = Arc::new(self);
+        let task = tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let server = server.clone();
+                tokio::spawn(async move {
+                    // 接続ごとの失敗はその接続を閉じるだけにする
+                    let _ = server.serve(stream).await;
+                });
+            }
+        });
+        Ok(MockHandle { addr, task })
+    }
+
+    async fn serve(self: Arc<Self>, stream: TcpStream) -> anyhow::Result<()> {
+        let mut stream = BufReader::new(stream);
+        let mut line = String::new();
+        stream.read_line(&mut line).await?;
+        let method = line
+            .split_whitespace()
+            .next()
+            .unwrap_or_default()
+            .to_owned();
+        let mut length = 0;
+        loop {
+            line.clear();
+            if stream.read_line(&mut line).await? == 0 || line.trim_end().is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                if name.eq_ignore_ascii_case("content-length") {
+                    length = value.trim().parse()?;
+                }
+            }
+        }
+        let mut body = vec![0; length];
+        stream.read_exact(&mut body).await?;
+
+        let (status, body) = if method != "POST" {
+            ("405 Method Not Allowed", String::new())
+        } else if !self.admit() {
+            ("200 OK", String::new())
+        } else {
+            let body = String::from_utf8(body)?;
+            let server = self.clone();
+            let reply = tokio::task::spawn_blocking(move || server.respond(&body)).await??;
+            ("200 OK", reply)
+        };
+        let response = format!(
+            "HTTP/1.1 {status}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        );
+        stream.get_mut().write_all(response.as_bytes()).await?;
+        stream.get_mut().shutdown().await?;
+        Ok(())
+    }
+
+    /// レート制限の範囲内なら数えて true を返す
+    fn admit(&self) -> bool {
+        let mut recent = self.recent.lock().unwrap();
+        let now = Instant::now();
+        while recent
+            .front()
+            .is_some_and(|&t| now.duration_since(t) >= Duration::from_secs(60))
+        {
+            recent.pop_front();
+        }
+        if recent.len() >= self.requests_per_minute {
+            return false;
+        }
+        recent.push_back(now);
+        true
+    }
+
+    /// エンコードされたリクエストに対する応答の本文
+    pub fn respond(&self, encoded: &str) -> anyhow::Result<String> {
+        let reply = match crate::eval_encoded(encoded) {
+            Ok(message) => self.command(&message, encoded.len()),
+            Err(err) => Err(err.context("Invalid expression")),
+        };
+        match reply {
+            Ok(Reply::Raw(body)) => Ok(body),
+            Ok(Reply::Text(text)) => token::encode(&[Token::String(text)]),
+            Err(err) => token::encode(&[Token::String(format!("{err:#}"))]),
+        }
+    }
+
+    fn command(&self, message: &str, request_len: usize) -> anyhow::Result<Reply> {
+        let (command, rest) = message.split_once(' ').unwrap_or((message, ""));
+        Ok(match command {
+            "echo" => Reply::Text(format!(
+                "{rest}\n\nYou scored some points for using the echo service!\n"
+            )),
+            "get" if rest == "index" => Reply::Text(self.index()?),
+            "get" if rest.starts_with("scoreboard") => {
+                Reply::Text(self.scoreboard(rest["scoreboard".len()..].trim())?)
+            }
+            "get" if self.families()?.contains_key(rest) => Reply::Text(self.family(rest)?),
+            "get" => {
+                let (family, _) = self.find(rest)?;
+                Reply::Raw(fs::read_to_string(
+                    self.data.join(&family).join(format!("{rest}.raw")),
+                )?)
+            }
+            "solve" => {
+                // 3d は `solve 3dN` の次の行から盤面が続く
+                let (task, answer) = rest
+                    .split_once(char::is_whitespace)
+                    .context("Expected 'solve <task> <answer>'")?;
+                Reply::Text(self.solve(task, answer, request_len)?)
+            }
+            _ => anyhow::bail!("Unknown command: {command}"),
+        })
+    }
+
+    /// 課題の種類ごとの課題の名前（番号順）
+    fn families(&self) -> anyhow::Result<BTreeMap<String, Vec<String>>> {
+        let mut families = BTreeMap::new();
+        for entry in fs::read_dir(&self.data)? {
+            let entry = entry?;
+            if !entry.file_type()?.is_dir() {
+                continue;
+            }
+            let family = entry.file_name().to_string_lossy().into_owned();
+            let mut tasks = vec![];
+            for file in fs::read_dir(entry.path())? {
+                let path = file?.path();
+                if path.extension().is_some_and(|ext| ext == "raw") {
+                    if let Some(n) = path
+                        .file_stem()
+                        .and_then(|stem| stem.to_str()?.strip_prefix(&family)?.parse::<u32>().ok())
+                    {
+                        tasks.push(n);
+                    }
+                }
+            }
+            if !tasks.is_empty() {
+                tasks.sort();
+                families.insert(
+                    family.clone(),
+                    tasks.iter().map(|n| format!("{family}{n}")).collect(),
+                );
+            }
+        }
+        Ok(families)
+    }
+
+    fn find(&self, task: &str) -> anyhow::Result<(String, Vec<String>)> {
+        self.families()?
+            .into_iter()
+            .find(|(_, tasks)| tasks.iter().any(|t| t == task))
+            .with_context(|| format!("Unknown task: {task}"))
+    }
+
+    fn index(&self) -> anyhow::Result<String> {
+        let mut page = "Mock server: the following families are available offline.\n\n".to_owned();
+        for family in self.families()?.keys() {
+            page.push_str(&format!("* [{family}]\n"));
+        }
+        Ok(page)
+    }
+
+    fn family(&self, family: &str) -> anyhow::Result<String> {
+        let scores = self.scores.lock().unwrap();
+        let mut page = format!("Mock server: {family} tasks.\n\n");
+        for task in &self.families()?[family] {
+            match scores.get(task) {
+                Some(score) => page.push_str(&format!(
+                    "* [{task}] Your score: {score}. Best score: {score}.\n"
+                )),
+                None => page.push_str(&format!("* [{task}]\n")),
+            }
+        }
+        Ok(page)
+    }
+
+    /// 参加チームは自分 (`mock`) だけ。種類ごとの列は解いた課題のスコアの合計
+    fn scoreboard(&self, family: &str) -> anyhow::Result<String> {
+        let families = self.families()?;
+        let scores = self.scores.lock().unwrap();
+        let total = |family: &str| -> usize {
+            families[family]
+                .iter()
+                .filter_map(|task| scores.get(task))
+                .sum()
+        };
+        let (header, cells) = if family.is_empty() {
+            (
+                families.keys().cloned().collect::<Vec<_>>(),
+                families.keys().map(|f| total(f)).collect::<Vec<_>>(),
+            )
+        } else {
+            anyhow::ensure!(families.contains_key(family), "Unknown family: {family}");
+            (vec!["score".to_owned()], vec![total(family)])
+        };
+        let mut page = format!("| # | team | {} |\n", header.join(" | "));
+        page.push_str(&format!("|---|---|{}\n", "---|".repeat(header.len())));
+        page.push_str(&format!(
+            "| 1 | mock | {} |\n",
+            cells
+                .iter()
+                .map(|n| n.to_string())
+                .collect::<Vec<_>>()
+                .join(" | ")
+        ));
+        Ok(page)
+    }
+
+    /// Lambdaman はリクエストのバイト数 `request_len`、Spaceship は移動回数がスコア
+    fn solve(&self, task: &str, answer: &str, request_len: usize) -> anyhow::Result<String> {
+        let (family, _) = self.find(task)?;
+        let input = || fs::read_to_string(self.data.join(&family).join(format!("{task}.in")));
+        let (remaining, score) = match family.as_str() {
+            "lambdaman" => (input()?.parse::<Map>()?.simulate(answer), request_len),
+            "spaceship" => (input()?.parse::<Problem>()?.simulate(answer), answer.len()),
+            _ => return Ok(format!("Mock server cannot check {family} solutions.")),
+        };
+        Ok(match remaining {
+            Ok(0) => {
+                let mut scores = self.scores.lock().unwrap();
+                let best = scores.entry(task.to_owned()).or_insert(score);
+                *best = score.min(*best);
+                format!("Correct, you solved {task} with a score of {score}!")
+            }
+            Ok(remaining) => {
+                format!("Your solution for {task} is wrong: {remaining} left unvisited.")
+            }
+            Err(err) => format!("Your solution for {task} is invalid: {err}"),
+        })
+    }
+}
+
+/// 待ち受け中のサーバー（捨てると止まる）
+pub struct MockHandle {
+    addr: SocketAddr,
+    task: JoinHandle<()>,
+}
+
+impl MockHandle {
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// `Config::new` に渡す URL
+    pub fn url(&self) -> String {
+        format!("http://{}/communicate", self.addr)
+    }
+
+    /// 止められるまで待ち受ける
+    pub async fn wait(mut self) -> anyhow::Result<()> {
+        (&mut self.task).await?;
+        Ok(())
+    }
+}
+
+impl Drop for MockHandle {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
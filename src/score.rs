@@ -0,0 +1,101 @@
+//! 手元の解のスコア（どれも小さいほど良い）
+//!
+//! Lambdaman は送るリクエストのバイト数、Spaceship は移動回数、3D は時空間の体積。
+//! Lambdaman の `.raw` は評価すると `solve` のリクエストになるプログラムで、そのまま送る。
+//! どれも自前のシミュレータで正しいか確かめてから数える（3D は課題文の例を通す）。
+
+use std::{fs, path::Path};
+
+use anyhow::{bail, Context};
+
+use crate::{
+    lambdaman::Map,
+    spaceship::Problem,
+    tasks::split_task,
+    threed,
+    token::{self, encode_string},
+};
+
+/// `solve <task> <answer>` を圧縮してエンコードしたリクエスト
+pub fn solve_request(task: &str, answer: &str) -> anyhow::Result<String> {
+    token::encode(&encode_string(&format!("solve {task} {answer}"))?)
+}
+
+/// スコアの数え方
+enum Metric {
+    /// 送るリクエストのバイト数
+    RequestSize,
+    Value(u64),
+}
+
+/// `data/<family>/<task>.in` に対する解 `answer` を確かめる（数えられない種類なら `None`）
+fn verify(data: &Path, task: &str, answer: &str) -> anyhow::Result<Option<Metric>> {
+    let (family, _) = split_task(task).with_context(|| format!("Invalid task: {task}"))?;
+    let input = || {
+        let path = data.join(family).join(format!("{task}.in"));
+        fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))
+    };
+    let answer = answer.trim();
+    match family {
+        "lambdaman" => {
+            let remaining = input()?.parse::<Map>()?.simulate(answer)?;
+            if remaining > 0 {
+                bail!("{remaining} pills left");
+            }
+            Ok(Some(Metric::RequestSize))
+        }
+        "spaceship" => {
+            let remaining = input()?.parse::<Problem>()?.simulate(answer)?;
+            if remaining > 0 {
+                bail!("{remaining} points left");
+            }
+            Ok(Some(Metric::Value(answer.len() as u64)))
+        }
+        "3d" => {
+            let grid = answer.parse::<threed::Grid>()?;
+            let examples = threed::examples(&input()?)?;
+            let score = threed::validate(&grid, &examples, threed::MAX_STEPS)?;
+            Ok(Some(Metric::Value(score.volume)))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// 解のファイルから送るリクエストを作る（`.raw` はそのまま、3D は盤面を改行のあとに続ける）
+pub fn file_request(task: &str, path: &Path) -> anyhow::Result<String> {
+    let text = fs::read_to_string(path)?;
+    if path.extension().is_some_and(|ext| ext == "raw") {
+        return Ok(text.trim().to_owned());
+    }
+    if task.starts_with("3d") {
+        let grid = threed::strip_header(&text).trim_end();
+        return token::encode(&encode_string(&format!("solve {task}\n{grid}"))?);
+    }
+    solve_request(task, text.trim())
+}
+
+/// `file_request` で作った `request` を使って解のファイルを確かめ、スコアを付ける
+///
+/// 圧縮したリクエストを作るのは重いので、リクエストの大きさを数えるときも作り直さない。
+pub fn request_score(
+    data: &Path,
+    task: &str,
+    path: &Path,
+    request: &str,
+) -> anyhow::Result<Option<u64>> {
+    let answer = if path.extension().is_some_and(|ext| ext == "raw") {
+        // 評価すると `solve <task> <moves>` になるプログラムをそのまま送る
+        let message = crate::eval_encoded(request)?;
+        message
+            .strip_prefix(&format!("solve {task} "))
+            .with_context(|| format!("Expected 'solve {task} ...'"))?
+            .to_owned()
+    } else {
+        fs::read_to_string(path)?
+    };
+    Ok(match verify(data, task, &answer)? {
+        Some(Metric::RequestSize) => Some(request.len() as u64),
+        Some(Metric::Value(score)) => Some(score),
+        None => None,
+    })
+}
@@ -0,0 +1,183 @@
+//! スコアボードと自分のスコアの記録
+//!
+//! `get scoreboard <family>` は `| # | team | ... |` の表を返し、
+//! `get <family>` は `* [lambdaman1] Your score: 33. Best score: 30.` のように課題ごとのスコアを返す。
+//! 取得したものはスナップショットとして JSONL に追記し、前回との差を比べる。
+
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    path::Path,
+};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use crate::tasks;
+
+/// スナップショットの既定の置き場所
+pub const DEFAULT_SNAPSHOTS: &str = "data/scoreboard.jsonl";
+
+/// `|` で区切られた表
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Table {
+    pub header: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    /// 最初の `|` で始まる行を見出しにし、`|---|` の区切り行は飛ばす
+    pub fn parse(page: &str) -> Table {
+        let mut lines = page
+            .lines()
+            .map(str::trim)
+            .filter(|line| line.starts_with('|'))
+            .map(|line| {
+                line.trim_matches('|')
+                    .split('|')
+                    .map(|cell| cell.trim().to_owned())
+                    .collect::<Vec<_>>()
+            })
+            .filter(|cells| {
+                !cells
+                    .iter()
+                    .all(|cell| !cell.is_empty() && cell.chars().all(|c| matches!(c, '-' | ':')))
+            });
+        Table {
+            header: lines.next().unwrap_or_default(),
+            rows: lines.collect(),
+        }
+    }
+
+    /// `team` の列が `team` の行
+    pub fn row(&self, team: &str) -> Option<&[String]> {
+        let column = self.column("team")?;
+        self.rows
+            .iter()
+            .find(|row| row.get(column).is_some_and(|cell| cell == team))
+            .map(Vec::as_slice)
+    }
+
+    pub fn column(&self, name: &str) -> Option<usize> {
+        self.header
+            .iter()
+            .position(|h| h.eq_ignore_ascii_case(name))
+    }
+
+    /// `#` の列（なければ何行目か）
+    pub fn rank(&self, team: &str) -> Option<usize> {
+        let row = self.row(team)?;
+        match self.column("#").and_then(|i| row.get(i)?.parse().ok()) {
+            Some(rank) => Some(rank),
+            None => self.rows.iter().position(|r| r == row).map(|i| i + 1),
+        }
+    }
+}
+
+/// 課題ごとの自分のスコアと全体の一番良いスコア（未提出なら `None`）
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TaskScore {
+    pub task: String,
+    pub ours: Option<u64>,
+    pub best: Option<u64>,
+}
+
+/// `get <family>` のページの `[task]` の行からスコアを読む
+pub fn task_scores(page: &str) -> Vec<TaskScore> {
+    let number_after = |line: &str, label: &str| {
+        let rest = &line[line.find(label)? + label.len()..];
+        let digits = rest
+            .trim_start()
+            .split(|c: char| !c.is_ascii_digit())
+            .next()?;
+        digits.parse().ok()
+    };
+    page.lines()
+        .filter_map(|line| {
+            let task = tasks::bracketed(line)
+                .into_iter()
+                .find(|name| tasks::split_task(name).is_some())?;
+            Some(TaskScore {
+                task,
+                ours: number_after(line, "Your score:"),
+                best: number_after(line, "Best score:"),
+            })
+        })
+        .collect()
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FamilySnapshot {
+    pub family: String,
+    pub scoreboard: Table,
+    pub scores: Vec<TaskScore>,
+}
+
+impl FamilySnapshot {
+    pub fn score(&self, task: &str) -> Option<&TaskScore> {
+        self.scores.iter().find(|score| score.task == task)
+    }
+}
+
+/// 1 回に取得したもの
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// 取得した時刻 (RFC 3339)
+    pub taken_at: String,
+    pub families: Vec<FamilySnapshot>,
+}
+
+impl Snapshot {
+    pub fn family(&self, family: &str) -> Option<&FamilySnapshot> {
+        self.families.iter().find(|f| f.family == family)
+    }
+}
+
+/// 1 行 1 スナップショットの JSONL を読む（なければ空）
+pub fn load_snapshots(path: &Path) -> anyhow::Result<Vec<Snapshot>> {
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+    fs::read_to_string(path)?
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            serde_json::from_str(line).with_context(|| format!("{}:{}", path.display(), i + 1))
+        })
+        .collect()
+}
+
+pub fn append_snapshot(path: &Path, snapshot: &Snapshot) -> anyhow::Result<()> {
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", serde_json::to_string(snapshot)?)?;
+    Ok(())
+}
+
+/// 前回からの自分のスコアの変化（小さいほど良い）
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Change {
+    Solved { task: String, score: u64 },
+    Improved { task: String, from: u64, to: u64 },
+    Worsened { task: String, from: u64, to: u64 },
+}
+
+pub fn changes(previous: Option<&FamilySnapshot>, current: &FamilySnapshot) -> Vec<Change> {
+    current
+        .scores
+        .iter()
+        .filter_map(|score| {
+            let task = score.task.clone();
+            let to = score.ours?;
+            match previous.and_then(|p| p.score(&score.task)?.ours) {
+                None => Some(Change::Solved { task, score: to }),
+                Some(from) if to < from => Some(Change::Improved { task, from, to }),
+                Some(from) if to > from => Some(Change::Worsened { task, from, to }),
+                Some(_) => None,
+            }
+        })
+        .collect()
+}
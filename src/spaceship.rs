@@ -0,0 +1,59 @@
+use std::{collections::HashSet, str::FromStr};
+
+use anyhow::{bail, Context};
+
+/// 1 回の提出で許される移動回数の上限
+pub const MAX_MOVES: usize = 10_000_000;
+
+/// `data/spaceship/*.in` の訪れるべき点
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Problem {
+    points: Vec<(i64, i64)>,
+}
+
+impl FromStr for Problem {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let points = s
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                let (x, y) = line
+                    .trim()
+                    .split_once(' ')
+                    .with_context(|| format!("Expected 'x y': {line}"))?;
+                Ok((x.parse()?, y.trim().parse()?))
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Problem { points })
+    }
+}
+
+impl Problem {
+    pub fn points(&self) -> &[(i64, i64)] {
+        &self.points
+    }
+
+    /// テンキーの数字の列で加速しながら進み、訪れていない点の数を返す
+    ///
+    /// 数字 d で速度を `((d - 1) % 3 - 1, (d - 1) / 3 - 1)` だけ変えてから位置に足す。
+    pub fn simulate(&self, moves: &str) -> anyhow::Result<usize> {
+        if moves.len() > MAX_MOVES {
+            bail!("Too many moves: {}", moves.len());
+        }
+        let mut unvisited = self.points.iter().copied().collect::<HashSet<_>>();
+        let (mut pos, mut vel) = ((0, 0), (0, 0));
+        unvisited.remove(&pos);
+        for c in moves.chars() {
+            let d = c
+                .to_digit(10)
+                .filter(|&d| d != 0)
+                .with_context(|| format!("Unexpected move: {c}"))? as i64;
+            vel = (vel.0 + (d - 1) % 3 - 1, vel.1 + (d - 1) / 3 - 1);
+            pos = (pos.0 + vel.0, pos.1 + vel.1);
+            unvisited.remove(&pos);
+        }
+        Ok(unvisited.len())
+    }
+}
@@ -0,0 +1,167 @@
+//! サーバーの課題のページの読み取りと課題のダウンロード、ダウンロードした課題のマニフェスト
+//!
+//! `get index` や `get lambdaman` のページは `[lambdaman]` `[lambdaman1]` のように
+//! 名前を角括弧で囲んで並べている。
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ast::Expr,
+    client::Client,
+    eval_encoded,
+    token::{self, Token},
+};
+
+/// `data/` の下のマニフェストのファイル名
+pub const MANIFEST: &str = "manifest.json";
+
+/// `[name]` の中身を出てきた順に（重複は除く）
+pub fn bracketed(page: &str) -> Vec<String> {
+    page.split('[')
+        .skip(1)
+        .filter_map(|s| s.split_once(']').map(|(name, _)| name))
+        .filter(|name| !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric()))
+        .map(str::to_owned)
+        .unique()
+        .collect()
+}
+
+/// `get index` のページの `* [name]` の行から課題の種類を読む
+///
+/// 本文中の `[index]` `[echo]` `[scoreboard]` などは課題の種類ではないので読まない。
+pub fn families(index: &str) -> Vec<String> {
+    index
+        .lines()
+        .filter_map(|line| line.trim_start().strip_prefix("* "))
+        .filter_map(|item| bracketed(item).into_iter().next())
+        .filter(|name| split_task(name).is_none())
+        .unique()
+        .collect()
+}
+
+/// 課題の種類のページから課題名を番号順に読む
+pub fn tasks(family: &str, page: &str) -> Vec<String> {
+    bracketed(page)
+        .into_iter()
+        .filter_map(|name| {
+            let (f, n) = split_task(&name)?;
+            (f == family).then_some(n)
+        })
+        .sorted()
+        .dedup()
+        .map(|n| format!("{family}{n}"))
+        .collect()
+}
+
+/// `lambdaman12` を `("lambdaman", 12)` に分ける（番号がなければ `None`）
+pub fn split_task(task: &str) -> Option<(&str, u32)> {
+    let family = task.trim_end_matches(|c: char| c.is_ascii_digit());
+    let number = task[family.len()..].parse().ok()?;
+    (!family.is_empty()).then_some((family, number))
+}
+
+/// ダウンロードして保存したファイル
+#[derive(Debug)]
+pub struct Downloaded {
+    /// サーバーの応答そのまま
+    pub raw: String,
+    pub raw_file: PathBuf,
+    /// `.in` か `.ast`（作れなかったらその理由）
+    pub decoded_file: anyhow::Result<PathBuf>,
+}
+
+/// 課題 `task` を `dir/<task>.raw` に保存し、評価した `.in` か efficiency なら `.ast` も書く
+pub async fn download(
+    client: &Client,
+    family: &str,
+    task: &str,
+    dir: &Path,
+) -> anyhow::Result<Downloaded> {
+    let request = token::encode(&[Token::String(format!("get {task}"))])?;
+    // 応答はそのまま残す
+    let raw = client.send_raw(&request).await?;
+    let raw_file = dir.join(format!("{task}.raw"));
+    fs::write(&raw_file, &raw)?;
+    let decoded_file = if family == "efficiency" {
+        // 評価するのが課題なので評価せずに木にする
+        let ast_file = dir.join(format!("{task}.ast"));
+        let ast = Expr::from_tokens(&token::decode_token_stream(&raw)?)?;
+        fs::write(&ast_file, format!("{ast}\n"))?;
+        Ok(ast_file)
+    } else {
+        let task_file = dir.join(format!("{task}.in"));
+        eval_encoded(&raw).and_then(|text| {
+            fs::write(&task_file, text)?;
+            Ok(task_file)
+        })
+    };
+    Ok(Downloaded {
+        raw,
+        raw_file,
+        decoded_file,
+    })
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    pub family: String,
+    /// 取得した時刻 (RFC 3339)
+    pub fetched_at: String,
+    /// `.raw` のバイト数
+    pub size: usize,
+}
+
+/// `data/manifest.json` に課題ごとの取得時刻と大きさを残す
+pub struct Manifest {
+    path: PathBuf,
+    entries: HashMap<String, ManifestEntry>,
+}
+
+impl Manifest {
+    /// `data` のマニフェストを読む（なければ空）
+    pub fn load(data: &Path) -> anyhow::Result<Self> {
+        let path = data.join(MANIFEST);
+        let entries = if path.exists() {
+            serde_json::from_str(&fs::read_to_string(&path)?)
+                .with_context(|| format!("Failed to parse {}", path.display()))?
+        } else {
+            HashMap::new()
+        };
+        Ok(Manifest { path, entries })
+    }
+
+    pub fn get(&self, task: &str) -> Option<&ManifestEntry> {
+        self.entries.get(task)
+    }
+
+    pub fn insert(&mut self, task: &str, entry: ManifestEntry) {
+        self.entries.insert(task.to_owned(), entry);
+    }
+
+    /// 差分が見やすいように 1 課題 1 行で、種類と番号の順に書く
+    pub fn save(&self) -> anyhow::Result<()> {
+        let lines = self
+            .entries
+            .iter()
+            .sorted_by_key(|(task, _)| split_task(task).unwrap_or((task.as_str(), 0)))
+            .map(|(task, entry)| {
+                Ok(format!(
+                    "  {}: {}",
+                    serde_json::to_string(task)?,
+                    serde_json::to_string(entry)?
+                ))
+            })
+            .collect::<serde_json::Result<Vec<_>>>()?
+            .join(",\n");
+        fs::write(&self.path, format!("{{\n{lines}\n}}\n"))?;
+        Ok(())
+    }
+}
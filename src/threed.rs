@@ -0,0 +1,697 @@
+//! 3D 言語のプログラム（盤面）
+//!
+//! 1 行が盤面の 1 行で、マスは空白で区切る。
+//! `.` は空き、`-99` から `99` までの整数、`< > ^ v` は移動、`+ - * / %` は演算、
+//! `= #` は比較、`@` は時間を戻す、`S` は答え、`A` `B` は入力。
+//!
+//! 実行は公式の規則どおりで、時刻 1 の盤面から始めて 1 tick ごとに全部の演算子を同時に動かす。
+//! 演算子は入力のマスが全部そろったときだけ動き、入力を消して出力のマスに書く。
+//! `@` は `v` を `(x - dx, y - dy)` に書いて `dt` 前の盤面に戻る。
+//! `S` に値が書かれたらそれが答えで、止まる。
+//!
+//! 課題文 `data/3d/3dN.in` の `# Example` の例を全部通せば正しいとみなし、
+//! 時刻・幅・高さのそれぞれの最大の積（時空間の体積）をスコアにする。
+
+use std::{collections::BTreeMap, fmt, str::FromStr};
+
+use anyhow::{bail, Context};
+use num_bigint::BigInt;
+use num_traits::{ToPrimitive, Zero};
+use serde::{Serialize, Serializer};
+
+pub mod compile;
+pub mod optimize;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Cell {
+    Empty,
+    Integer(BigInt),
+    Left,
+    Right,
+    Up,
+    Down,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Warp,
+    Eq,
+    Ne,
+    Submit,
+    A,
+    B,
+}
+
+impl FromStr for Cell {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "." => Cell::Empty,
+            "<" => Cell::Left,
+            ">" => Cell::Right,
+            "^" => Cell::Up,
+            "v" => Cell::Down,
+            "+" => Cell::Add,
+            "-" => Cell::Sub,
+            "*" => Cell::Mul,
+            "/" => Cell::Div,
+            "%" => Cell::Mod,
+            "@" => Cell::Warp,
+            "=" => Cell::Eq,
+            "#" => Cell::Ne,
+            "S" => Cell::Submit,
+            "A" => Cell::A,
+            "B" => Cell::B,
+            _ => {
+                let n = s
+                    .parse::<i64>()
+                    .ok()
+                    .with_context(|| format!("Unknown cell: {s}"))?;
+                if !(-99..=99).contains(&n) {
+                    bail!("Integer out of range -99..=99: {n}");
+                }
+                Cell::Integer(n.into())
+            }
+        })
+    }
+}
+
+impl fmt::Display for Cell {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Cell::Empty => ".",
+            Cell::Integer(n) => return n.fmt(f),
+            Cell::Left => "<",
+            Cell::Right => ">",
+            Cell::Up => "^",
+            Cell::Down => "v",
+            Cell::Add => "+",
+            Cell::Sub => "-",
+            Cell::Mul => "*",
+            Cell::Div => "/",
+            Cell::Mod => "%",
+            Cell::Warp => "@",
+            Cell::Eq => "=",
+            Cell::Ne => "#",
+            Cell::Submit => "S",
+            Cell::A => "A",
+            Cell::B => "B",
+        };
+        f.write_str(s)
+    }
+}
+
+/// `rows[y][x]`（行の長さは揃えない）
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Grid {
+    pub rows: Vec<Vec<Cell>>,
+}
+
+impl FromStr for Grid {
+    type Err = anyhow::Error;
+
+    /// 先頭の `solve 3dN` の行と末尾の空行は読み飛ばす
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let body = strip_header(s);
+        let rows = body
+            .trim_end()
+            .lines()
+            .enumerate()
+            .map(|(y, line)| {
+                line.split_whitespace()
+                    .enumerate()
+                    .map(|(x, cell)| cell.parse().with_context(|| format!("At ({x}, {y})")))
+                    .collect::<anyhow::Result<Vec<_>>>()
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Grid { rows })
+    }
+}
+
+impl fmt::Display for Grid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for row in &self.rows {
+            let line = row.iter().map(Cell::to_string).collect::<Vec<_>>();
+            writeln!(f, "{}", line.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+/// `solve 3dN` の行を除いた盤面
+pub fn strip_header(s: &str) -> &str {
+    match s.trim_start().split_once('\n') {
+        Some((first, rest)) if first.starts_with("solve ") => rest,
+        _ => s,
+    }
+}
+
+impl Grid {
+    pub fn get(&self, x: usize, y: usize) -> Option<&Cell> {
+        self.rows.get(y)?.get(x)
+    }
+
+    pub fn width(&self) -> usize {
+        self.rows.iter().map(Vec::len).max().unwrap_or(0)
+    }
+
+    pub fn height(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// 空きでないマスの位置
+    pub fn cells(&self) -> impl Iterator<Item = ((usize, usize), &Cell)> + '_ {
+        self.rows.iter().enumerate().flat_map(|(y, row)| {
+            row.iter()
+                .enumerate()
+                .filter(|(_, cell)| **cell != Cell::Empty)
+                .map(move |(x, cell)| ((x, y), cell))
+        })
+    }
+
+    /// 実行しなくてもわかる誤り（`S` がない）を調べる
+    pub fn check(&self) -> anyhow::Result<()> {
+        if self.cells().all(|(_, cell)| *cell != Cell::Submit) {
+            bail!("No S in the grid");
+        }
+        Ok(())
+    }
+}
+
+/// 盤面の位置 `(x, y)`（右と下が正で、実行中は負にもなる）
+pub type Pos = (i64, i64);
+
+/// 実行中の盤面（空きのマスは持たない）
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Board {
+    pub cells: BTreeMap<Pos, Cell>,
+}
+
+impl Board {
+    /// `A` と `B` を入力の値に置き換えて時刻 1 の盤面にする
+    pub fn new(grid: &Grid, a: &BigInt, b: &BigInt) -> Board {
+        let cells = grid
+            .cells()
+            .map(|((x, y), cell)| {
+                let cell = match cell {
+                    Cell::A => Cell::Integer(a.clone()),
+                    Cell::B => Cell::Integer(b.clone()),
+                    cell => cell.clone(),
+                };
+                ((x as i64, y as i64), cell)
+            })
+            .collect();
+        Board { cells }
+    }
+
+    pub fn get(&self, pos: Pos) -> Option<&Cell> {
+        self.cells.get(&pos)
+    }
+
+    /// 空きでないマスを囲む範囲 `((min_x, min_y), (max_x, max_y))`
+    pub fn bounds(&self) -> Option<(Pos, Pos)> {
+        let mut positions = self.cells.keys();
+        let &(x, y) = positions.next()?;
+        Some(
+            positions.fold(((x, y), (x, y)), |((x0, y0), (x1, y1)), &(x, y)| {
+                ((x0.min(x), y0.min(y)), (x1.max(x), y1.max(y)))
+            }),
+        )
+    }
+}
+
+/// 可視化用の盤面（範囲の左上の位置と各行のマスの文字列）
+#[derive(Serialize)]
+struct BoardJson {
+    x: Option<i64>,
+    y: Option<i64>,
+    rows: Vec<Vec<String>>,
+}
+
+impl Serialize for Board {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let Some(((x0, y0), (x1, y1))) = self.bounds() else {
+            let empty = BoardJson {
+                x: None,
+                y: None,
+                rows: vec![],
+            };
+            return empty.serialize(serializer);
+        };
+        let rows = (y0..=y1)
+            .map(|y| {
+                (x0..=x1)
+                    .map(|x| self.get((x, y)).unwrap_or(&Cell::Empty).to_string())
+                    .collect()
+            })
+            .collect();
+        BoardJson {
+            x: Some(x0),
+            y: Some(y0),
+            rows,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl fmt::Display for Board {
+    /// 空きでないマスを囲む範囲を `Grid` と同じ形で
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Some(((x0, y0), (x1, y1))) = self.bounds() else {
+            return Ok(());
+        };
+        for y in y0..=y1 {
+            let line = (x0..=x1)
+                .map(|x| self.get((x, y)).unwrap_or(&Cell::Empty).to_string())
+                .collect::<Vec<_>>();
+            writeln!(f, "{}", line.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+/// 1 tick で起きたこと
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Tick {
+    /// 動かした盤面の時刻
+    pub from: usize,
+    /// 次の盤面の時刻（`@` で戻ったときは `from` 以下）
+    pub to: usize,
+    /// 書いたマス（`@` で戻ったときは戻った先に書いたもの）
+    pub writes: Vec<(Pos, Cell)>,
+    /// `S` に書かれた値
+    pub submitted: Option<BigInt>,
+}
+
+impl Tick {
+    /// `@` で前の時刻に戻ったか
+    pub fn is_warp(&self) -> bool {
+        self.to < self.from
+    }
+}
+
+/// 可視化用の 1 tick（書いた値と答えは桁が大きくなるので文字列にする）
+#[derive(Serialize)]
+struct TickJson {
+    from: usize,
+    to: usize,
+    warp: bool,
+    writes: Vec<WriteJson>,
+    submitted: Option<String>,
+}
+
+#[derive(Serialize)]
+struct WriteJson {
+    x: i64,
+    y: i64,
+    cell: String,
+}
+
+impl Serialize for Tick {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let writes = self
+            .writes
+            .iter()
+            .map(|&((x, y), ref cell)| WriteJson {
+                x,
+                y,
+                cell: cell.to_string(),
+            })
+            .collect();
+        TickJson {
+            from: self.from,
+            to: self.to,
+            warp: self.is_warp(),
+            writes,
+            submitted: self.submitted.as_ref().map(BigInt::to_string),
+        }
+        .serialize(serializer)
+    }
+}
+
+/// 止まったときの答えと、時空間の大きさ（小さいほど良い）
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Outcome {
+    pub answer: BigInt,
+    /// 盤面の時刻の最大
+    pub ticks: usize,
+    /// 実行した tick の数（`@` で戻った分も数える）
+    pub steps: usize,
+    pub width: u64,
+    pub height: u64,
+    /// `ticks * width * height`
+    pub volume: u64,
+}
+
+/// 実行を打ち切る tick の数の既定値
+pub const MAX_STEPS: usize = 1_000_000;
+
+pub struct Simulator {
+    /// `history[t - 1]` が時刻 `t` の盤面
+    history: Vec<Board>,
+    steps: usize,
+    max_steps: usize,
+    max_tick: usize,
+    bounds: Option<(Pos, Pos)>,
+}
+
+impl Simulator {
+    pub fn new(grid: &Grid, a: &BigInt, b: &BigInt) -> Simulator {
+        let board = Board::new(grid, a, b);
+        Simulator {
+            bounds: board.bounds(),
+            history: vec![board],
+            steps: 0,
+            max_steps: MAX_STEPS,
+            max_tick: 1,
+        }
+    }
+
+    pub fn with_max_steps(mut self, max_steps: usize) -> Simulator {
+        self.max_steps = max_steps;
+        self
+    }
+
+    pub fn board(&self) -> &Board {
+        self.history.last().unwrap()
+    }
+
+    /// 今の盤面の時刻（1 から）
+    pub fn tick(&self) -> usize {
+        self.history.len()
+    }
+
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+
+    /// 1 tick 進める
+    pub fn step(&mut self) -> anyhow::Result<Tick> {
+        if self.steps >= self.max_steps {
+            bail!("Exceeded {} ticks", self.max_steps);
+        }
+        self.steps += 1;
+        let from = self.tick();
+        let board = self.board();
+        let mut removes = vec![];
+        let mut writes = Writes::default();
+        let mut warps = Writes::default();
+        let mut warp_dt = None;
+        for (&(x, y), cell) in &board.cells {
+            let at = |dx: i64, dy: i64| board.get((x + dx, y + dy));
+            let (dx, dy) = match cell {
+                Cell::Left => (-1, 0),
+                Cell::Right => (1, 0),
+                Cell::Up => (0, -1),
+                Cell::Down => (0, 1),
+                _ => (0, 0),
+            };
+            match cell {
+                Cell::Left | Cell::Right | Cell::Up | Cell::Down => {
+                    let Some(value) = at(-dx, -dy) else { continue };
+                    removes.push((x - dx, y - dy));
+                    writes.write(board, (x + dx, y + dy), value.clone())?;
+                }
+                Cell::Add | Cell::Sub | Cell::Mul | Cell::Div | Cell::Mod => {
+                    let (Some(Cell::Integer(l)), Some(Cell::Integer(u))) = (at(-1, 0), at(0, -1))
+                    else {
+                        continue;
+                    };
+                    if matches!(cell, Cell::Div | Cell::Mod) && u.is_zero() {
+                        bail!("Division by zero at ({x}, {y}) on tick {from}");
+                    }
+                    let value = match cell {
+                        Cell::Add => l + u,
+                        Cell::Sub => l - u,
+                        Cell::Mul => l * u,
+                        // どちらも 0 の方へ切り捨てる
+                        Cell::Div => l / u,
+                        _ => l % u,
+                    };
+                    removes.extend([(x - 1, y), (x, y - 1)]);
+                    writes.write(board, (x + 1, y), Cell::Integer(value.clone()))?;
+                    writes.write(board, (x, y + 1), Cell::Integer(value))?;
+                }
+                Cell::Eq | Cell::Ne => {
+                    let (Some(l), Some(u)) = (at(-1, 0), at(0, -1)) else {
+                        continue;
+                    };
+                    if (l == u) != (*cell == Cell::Eq) {
+                        continue;
+                    }
+                    removes.extend([(x - 1, y), (x, y - 1)]);
+                    writes.write(board, (x + 1, y), u.clone())?;
+                    writes.write(board, (x, y + 1), l.clone())?;
+                }
+                Cell::Warp => {
+                    let (
+                        Some(Cell::Integer(dx)),
+                        Some(v),
+                        Some(Cell::Integer(dy)),
+                        Some(Cell::Integer(dt)),
+                    ) = (at(-1, 0), at(0, -1), at(1, 0), at(0, 1))
+                    else {
+                        continue;
+                    };
+                    let context = || format!("Time warp at ({x}, {y}) on tick {from}");
+                    let offset = |n: &BigInt| {
+                        n.to_i64()
+                            .context("Offset out of range")
+                            .with_context(context)
+                    };
+                    let dt = dt
+                        .to_usize()
+                        .filter(|&dt| dt >= 1)
+                        .with_context(|| format!("Invalid dt: {dt}"))
+                        .with_context(context)?;
+                    if warp_dt.is_some_and(|t| t != dt) {
+                        bail!("Time warps to different ticks on tick {from}");
+                    }
+                    warp_dt = Some(dt);
+                    warps.warp((x - offset(dx)?, y - offset(dy)?), v.clone())?;
+                }
+                _ => {}
+            }
+        }
+
+        let submitted = |board: &Board, writes: &Writes| {
+            let values = writes
+                .0
+                .iter()
+                .filter(|&(&pos, _)| board.get(pos) == Some(&Cell::Submit))
+                .map(|(_, cell)| match cell {
+                    Cell::Integer(n) => Ok(n.clone()),
+                    cell => Err(anyhow::anyhow!("Submitted a non-integer: {cell}")),
+                })
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            if values.iter().any(|v| *v != values[0]) {
+                bail!("Submitted different values on tick {from}");
+            }
+            Ok(values.into_iter().next())
+        };
+
+        if writes.0.is_empty() && warp_dt.is_none() {
+            // 何も書かなければ盤面はもう変わらない
+            bail!("Stuck on tick {from}");
+        }
+        // 同じ tick に `S` に書かれていれば `@` より先に止まる
+        let submitted_now = submitted(board, &writes)?;
+        let (to, writes, submitted) = match warp_dt {
+            Some(dt) if submitted_now.is_none() => {
+                if dt >= from {
+                    bail!(
+                        "Time warp to tick {} on tick {from}",
+                        from as i64 - dt as i64
+                    );
+                }
+                self.history.truncate(from - dt);
+                let board = self.history.last_mut().unwrap();
+                let submitted = submitted(board, &warps)?;
+                for (pos, cell) in &warps.0 {
+                    board.cells.insert(*pos, cell.clone());
+                }
+                (from - dt, warps, submitted)
+            }
+            _ => {
+                let mut next = board.clone();
+                for pos in &removes {
+                    next.cells.remove(pos);
+                }
+                for (pos, cell) in &writes.0 {
+                    next.cells.insert(*pos, cell.clone());
+                }
+                self.history.push(next);
+                (from + 1, writes, submitted_now)
+            }
+        };
+        if submitted.is_none() {
+            // 答えを書いた盤面は数えない
+            self.max_tick = self.max_tick.max(to);
+            if let Some((min, max)) = self.board().bounds() {
+                self.bounds = Some(match self.bounds {
+                    Some((lo, hi)) => (
+                        (lo.0.min(min.0), lo.1.min(min.1)),
+                        (hi.0.max(max.0), hi.1.max(max.1)),
+                    ),
+                    None => (min, max),
+                });
+            }
+        }
+        Ok(Tick {
+            from,
+            to,
+            writes: writes.0.into_iter().collect(),
+            submitted,
+        })
+    }
+
+    /// 答えが書かれるまで進める
+    pub fn run(&mut self) -> anyhow::Result<Outcome> {
+        loop {
+            let tick = self.step()?;
+            if let Some(answer) = tick.submitted {
+                let ((x0, y0), (x1, y1)) = self.bounds.unwrap_or_default();
+                let (width, height) = ((x1 - x0 + 1) as u64, (y1 - y0 + 1) as u64);
+                return Ok(Outcome {
+                    answer,
+                    ticks: self.max_tick,
+                    steps: self.steps,
+                    width,
+                    height,
+                    volume: self.max_tick as u64 * width * height,
+                });
+            }
+        }
+    }
+}
+
+/// 同じマスに違う値を書いたら誤り
+#[derive(Default)]
+struct Writes(BTreeMap<Pos, Cell>);
+
+impl Writes {
+    /// 演算子の書き込み。同じマスへは同じ値でも書けない（`S` に同じ値を出すのだけはよい）
+    fn write(&mut self, board: &Board, pos: Pos, cell: Cell) -> anyhow::Result<()> {
+        let submit = board.get(pos) == Some(&Cell::Submit);
+        self.insert(pos, cell, submit)
+    }
+
+    /// `@` の書き込み。同じ値なら同じマスへ書いてよい
+    fn warp(&mut self, pos: Pos, cell: Cell) -> anyhow::Result<()> {
+        self.insert(pos, cell, true)
+    }
+
+    fn insert(&mut self, pos: Pos, cell: Cell, allow_same: bool) -> anyhow::Result<()> {
+        match self.0.get(&pos) {
+            Some(old) if !allow_same || *old != cell => {
+                bail!(
+                    "Conflicting writes at ({}, {}): {old} and {cell}",
+                    pos.0,
+                    pos.1
+                )
+            }
+            Some(_) => {}
+            None => {
+                self.0.insert(pos, cell);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `grid` を `A = a`, `B = b` で実行する
+pub fn run(grid: &Grid, a: &BigInt, b: &BigInt) -> anyhow::Result<Outcome> {
+    Simulator::new(grid, a, b).run()
+}
+
+/// 課題文の例（`B` がなければ 0）
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Example {
+    pub a: BigInt,
+    pub b: BigInt,
+    pub answer: BigInt,
+}
+
+/// 課題文の `# Example` から `` * `A = 5` `` で始まる例を読む
+pub fn examples(statement: &str) -> anyhow::Result<Vec<Example>> {
+    let mut examples = vec![];
+    let mut current: Option<(BigInt, BigInt, Option<BigInt>)> = None;
+    let section = statement
+        .split_once("# Example")
+        .context("No examples in the statement")?
+        .1;
+    for line in section.lines() {
+        let line = line.trim();
+        let Some((name, value)) = line
+            .trim_start_matches('*')
+            .trim()
+            .trim_matches('`')
+            .split_once(" = ")
+        else {
+            continue;
+        };
+        let value = value
+            .trim()
+            .parse::<BigInt>()
+            .with_context(|| format!("Invalid example: {line}"))?;
+        match name {
+            "A" => {
+                examples.extend(current.take().and_then(Example::complete));
+                current = Some((value, BigInt::zero(), None));
+            }
+            "B" => current.as_mut().context("B before A")?.1 = value,
+            "Answer" => current.as_mut().context("Answer before A")?.2 = Some(value),
+            _ => bail!("Unknown example value: {line}"),
+        }
+    }
+    examples.extend(current.and_then(Example::complete));
+    if examples.is_empty() {
+        bail!("No examples in the statement");
+    }
+    Ok(examples)
+}
+
+impl Example {
+    fn complete((a, b, answer): (BigInt, BigInt, Option<BigInt>)) -> Option<Example> {
+        Some(Example {
+            a,
+            b,
+            answer: answer?,
+        })
+    }
+}
+
+/// 例を全部通したときの大きさ（それぞれ全部の例での最大）
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Score {
+    pub ticks: usize,
+    pub width: u64,
+    pub height: u64,
+    /// `ticks * width * height`
+    pub volume: u64,
+}
+
+/// 例を全部実行して答えを確かめる（`max_steps` で打ち切る）
+pub fn validate(grid: &Grid, examples: &[Example], max_steps: usize) -> anyhow::Result<Score> {
+    grid.check()?;
+    let mut score = Score::default();
+    for Example { a, b, answer } in examples {
+        let outcome = Simulator::new(grid, a, b)
+            .with_max_steps(max_steps)
+            .run()
+            .with_context(|| format!("A = {a}, B = {b}"))?;
+        if outcome.answer != *answer {
+            bail!(
+                "A = {a}, B = {b}: Expected {answer} but got {}",
+                outcome.answer
+            );
+        }
+        score.ticks = score.ticks.max(outcome.ticks);
+        score.width = score.width.max(outcome.width);
+        score.height = score.height.max(outcome.height);
+    }
+    score.volume = score.ticks as u64 * score.width * score.height;
+    Ok(score)
+}
@@ -0,0 +1,320 @@
+//! 小さな手続き型言語を 3D 言語の盤面にコンパイルする
+//!
+//! ```text
+//! # 階乗
+//! let n = A
+//! let acc = 1
+//! while n != 0 {
+//!     acc = acc * n
+//!     n = n - 1
+//! }
+//! return acc
+//! ```
+//!
+//! - 先頭に `let x = e` を並べ、`while l != r { ... }`（か `==`）を 1 つまで置き、`return e` で終わる
+//! - ループの中は `x = e` で変数を書き換え、`let t = e` で一時的な名前を付ける（上から順に実行する）
+//! - 式は整数・`A`・`B`・変数と `+ - * / %`（`/` `%` は 0 の方へ切り捨て）、前置の `-`、括弧
+//! - `#` から行末はコメント
+//!
+//! ループで書き換える変数は、使う場所ごとに初期値（`A` `B` か -99 から 99 の整数）のマスを置き、
+//! 新しい値を `@` で時刻 1 のそのマスに書き戻す。
+//! ループの条件と `return` の式は毎周計算し、抜けるときは `@` より前の tick に `S` に書く。
+//! 書き換えない変数は式に展開する。
+//! 条件と `return` の式はどの周でも計算するので、途中の周で 0 で割ると実行が止まる（ループの中の `/` `%` は抜ける周には計算しない）。
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt,
+};
+
+use anyhow::{bail, Context};
+use num_bigint::BigInt;
+use num_traits::Zero;
+
+use super::{Cell, Grid};
+
+mod layout;
+mod parser;
+
+pub use parser::parse;
+
+/// ソースコードの 1 始まりの行・列
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SourcePos {
+    pub line: usize,
+    pub col: usize,
+}
+
+impl fmt::Display for SourcePos {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.col)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+}
+
+impl Op {
+    pub fn cell(self) -> Cell {
+        match self {
+            Op::Add => Cell::Add,
+            Op::Sub => Cell::Sub,
+            Op::Mul => Cell::Mul,
+            Op::Div => Cell::Div,
+            Op::Mod => Cell::Mod,
+        }
+    }
+
+    /// 3D 言語と同じく 0 の方へ切り捨てる
+    pub fn apply(self, l: &BigInt, r: &BigInt) -> anyhow::Result<BigInt> {
+        if matches!(self, Op::Div | Op::Mod) && r.is_zero() {
+            bail!("Division by zero: {l} {} 0", self.cell());
+        }
+        Ok(match self {
+            Op::Add => l + r,
+            Op::Sub => l - r,
+            Op::Mul => l * r,
+            Op::Div => l / r,
+            Op::Mod => l % r,
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Term {
+    Int(BigInt),
+    A,
+    B,
+    Var(String, SourcePos),
+    Binary(Op, Box<Term>, Box<Term>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Stmt {
+    Let(String, Term),
+    Assign(String, Term, SourcePos),
+}
+
+/// `while lhs != rhs { body }`（`eq` なら `==` の間）
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Loop {
+    pub lhs: Term,
+    pub eq: bool,
+    pub rhs: Term,
+    pub body: Vec<Stmt>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Program {
+    pub lets: Vec<(String, Term)>,
+    pub main_loop: Option<Loop>,
+    pub result: Term,
+}
+
+fn eval(term: &Term, env: &HashMap<String, BigInt>) -> anyhow::Result<BigInt> {
+    Ok(match term {
+        Term::Int(n) => n.clone(),
+        Term::A => env["A"].clone(),
+        Term::B => env["B"].clone(),
+        Term::Var(name, pos) => env
+            .get(name)
+            .with_context(|| format!("Unknown variable {name} at {pos}"))?
+            .clone(),
+        Term::Binary(op, l, r) => op.apply(&eval(l, env)?, &eval(r, env)?)?,
+    })
+}
+
+/// 盤面にせずにそのまま実行する（ループが `max_iterations` 回を超えたら誤り）
+pub fn interpret(
+    program: &Program,
+    a: &BigInt,
+    b: &BigInt,
+    max_iterations: usize,
+) -> anyhow::Result<BigInt> {
+    let mut env = HashMap::from([("A".to_owned(), a.clone()), ("B".to_owned(), b.clone())]);
+    for (name, term) in &program.lets {
+        let value = eval(term, &env)?;
+        env.insert(name.clone(), value);
+    }
+    if let Some(main_loop) = &program.main_loop {
+        let mut iterations = 0;
+        while (eval(&main_loop.lhs, &env)? == eval(&main_loop.rhs, &env)?) == main_loop.eq {
+            iterations += 1;
+            if iterations > max_iterations {
+                bail!("Exceeded {max_iterations} iterations");
+            }
+            let mut locals = env.clone();
+            for stmt in &main_loop.body {
+                match stmt {
+                    Stmt::Let(name, term) => {
+                        let value = eval(term, &locals)?;
+                        locals.insert(name.clone(), value);
+                    }
+                    Stmt::Assign(name, term, pos) => {
+                        if !env.contains_key(name) {
+                            bail!("Unknown variable {name} at {pos}");
+                        }
+                        let value = eval(term, &locals)?;
+                        locals.insert(name.clone(), value.clone());
+                        env.insert(name.clone(), value);
+                    }
+                }
+            }
+        }
+    }
+    eval(&program.result, &env)
+}
+
+/// 盤面に置く前の式（定数は畳み込む）
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Expr {
+    /// `A` `B` か整数
+    Const(Cell),
+    /// ループで書き換える変数
+    Slot(String),
+    Op(Op, Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    fn binary(op: Op, l: Expr, r: Expr) -> anyhow::Result<Expr> {
+        if let (Expr::Const(Cell::Integer(l)), Expr::Const(Cell::Integer(r))) = (&l, &r) {
+            return Ok(Expr::Const(Cell::Integer(op.apply(l, r)?)));
+        }
+        Ok(Expr::Op(op, l.into(), r.into()))
+    }
+
+    fn slots<'a>(&'a self, slots: &mut Vec<&'a str>) {
+        match self {
+            Expr::Const(_) => {}
+            Expr::Slot(name) => slots.push(name),
+            Expr::Op(_, l, r) => {
+                l.slots(slots);
+                r.slots(slots);
+            }
+        }
+    }
+
+    /// 盤面の木にする（範囲外の整数は 99 進で組み立てる）
+    fn node(&self, inits: &HashMap<String, Cell>) -> layout::Node {
+        match self {
+            Expr::Const(Cell::Integer(n)) if n.magnitude() > &99u32.into() => {
+                let base = BigInt::from(99);
+                let high = Expr::Const(Cell::Integer(n / &base)).node(inits);
+                let mut node = layout::Node::Op(
+                    Cell::Mul,
+                    high.into(),
+                    layout::Node::Leaf(Cell::Integer(base.clone()), None).into(),
+                );
+                let low = n % &base;
+                if !low.is_zero() {
+                    let low = layout::Node::Leaf(Cell::Integer(low), None);
+                    node = layout::Node::Op(Cell::Add, node.into(), low.into());
+                }
+                node
+            }
+            Expr::Const(cell) => layout::Node::Leaf(cell.clone(), None),
+            Expr::Slot(name) => layout::Node::Leaf(inits[name].clone(), Some(name.clone())),
+            Expr::Op(op, l, r) => {
+                layout::Node::Op(op.cell(), l.node(inits).into(), r.node(inits).into())
+            }
+        }
+    }
+}
+
+fn lower(term: &Term, env: &HashMap<String, Expr>) -> anyhow::Result<Expr> {
+    Ok(match term {
+        Term::Int(n) => Expr::Const(Cell::Integer(n.clone())),
+        Term::A => Expr::Const(Cell::A),
+        Term::B => Expr::Const(Cell::B),
+        Term::Var(name, pos) => env
+            .get(name)
+            .with_context(|| format!("Unknown variable {name} at {pos}"))?
+            .clone(),
+        Term::Binary(op, l, r) => Expr::binary(*op, lower(l, env)?, lower(r, env)?)?,
+    })
+}
+
+/// 構文木を盤面にする
+pub fn compile_program(program: &Program) -> anyhow::Result<Grid> {
+    let mut env = HashMap::new();
+    for (name, term) in &program.lets {
+        let value = lower(term, &env)?;
+        env.insert(name.clone(), value);
+    }
+    let Some(main_loop) = &program.main_loop else {
+        let result = lower(&program.result, &env)?;
+        return layout::straight(&result.node(&HashMap::new()));
+    };
+
+    // ループで書き換える変数は初期値のマスから読む
+    let mut inits = HashMap::new();
+    let mut carried = vec![];
+    for stmt in &main_loop.body {
+        let Stmt::Assign(name, _, pos) = stmt else {
+            continue;
+        };
+        if carried.contains(name) {
+            continue;
+        }
+        let init = match env.get(name) {
+            None => bail!("Unknown variable {name} at {pos}"),
+            Some(Expr::Const(Cell::Integer(n))) if n.magnitude() <= &99u32.into() => {
+                Cell::Integer(n.clone())
+            }
+            Some(Expr::Const(cell @ (Cell::A | Cell::B))) => cell.clone(),
+            Some(_) => bail!(
+                "Initial value of {name} must be A, B or an integer in -99..=99 because it changes at {pos}"
+            ),
+        };
+        inits.insert(name.clone(), init);
+        carried.push(name.clone());
+    }
+    for name in &carried {
+        env.insert(name.clone(), Expr::Slot(name.clone()));
+    }
+    let lhs = lower(&main_loop.lhs, &env)?;
+    let rhs = lower(&main_loop.rhs, &env)?;
+    let result = lower(&program.result, &env)?;
+    let mut locals = env.clone();
+    for stmt in &main_loop.body {
+        let (Stmt::Let(name, term) | Stmt::Assign(name, term, _)) = stmt;
+        let value = lower(term, &locals)?;
+        locals.insert(name.clone(), value);
+    }
+
+    // 条件と答えから辿れる変数だけ書き戻す
+    let mut live = BTreeSet::new();
+    let mut queue = vec![];
+    for expr in [&lhs, &rhs, &result] {
+        expr.slots(&mut queue);
+    }
+    while let Some(name) = queue.pop() {
+        if live.insert(name.to_owned()) {
+            locals[name].slots(&mut queue);
+        }
+    }
+    let updates = carried
+        .iter()
+        .filter(|name| live.contains(*name))
+        .map(|name| (name.clone(), locals[name].node(&inits)))
+        .collect::<Vec<_>>();
+    // `while l != r` は等しくなったら抜ける
+    let gate = if main_loop.eq { Cell::Ne } else { Cell::Eq };
+    let exit = layout::Node::Op(
+        Cell::Add,
+        result.node(&inits).into(),
+        layout::Node::Zero(gate, lhs.node(&inits).into(), rhs.node(&inits).into()).into(),
+    );
+    layout::looped(&updates, &exit)
+}
+
+/// ソースコードを盤面にコンパイルする
+pub fn compile(src: &str) -> anyhow::Result<Grid> {
+    compile_program(&parse(src)?)
+}
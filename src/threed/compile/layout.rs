@@ -0,0 +1,365 @@
+//! 式の木を盤面に並べる
+//!
+//! 値は `>` と `v` と演算子でしか動かさないので、どの経路も 1 tick に 2 マス進み、
+//! 演算子が動く時刻は並べ方だけで決まる。
+//! 演算子の左の入力は同じ行を `>` で、上の入力は同じ列を `v` で運ぶ。
+
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::bail;
+use num_traits::Zero;
+
+use crate::threed::{Cell, Grid, Pos};
+
+pub enum Node {
+    /// 定数か、ループで書き換える変数の初期値（変数名付き）
+    Leaf(Cell, Option<String>),
+    /// 左と上の入力を演算子 1 つで
+    Op(Cell, Box<Node>, Box<Node>),
+    /// 比較 `=` か `#` が通ったときだけ 0 になる（`= -` か `# - %` を斜めに並べる）
+    Zero(Cell, Box<Node>, Box<Node>),
+}
+
+/// 並べた部品（位置は左上を `(0, 0)` とする）
+#[derive(Clone, Debug)]
+struct Block {
+    cells: Vec<(Pos, Cell)>,
+    width: i64,
+    height: i64,
+    /// 最後の演算子の右と下の出力（葉ならそのマス）
+    right: Pos,
+    down: Pos,
+    /// 出力に値がそろう時刻
+    ready: i64,
+    /// ループで書き換える変数を読むマス
+    slots: Vec<(String, Pos)>,
+    /// 変数に書き戻す `@`
+    warps: Vec<(String, Pos)>,
+}
+
+impl Block {
+    fn leaf(cell: Cell, slot: Option<String>) -> Block {
+        Block {
+            cells: vec![((0, 0), cell)],
+            width: 1,
+            height: 1,
+            right: (0, 0),
+            down: (0, 0),
+            ready: 1,
+            slots: slot.into_iter().map(|name| (name, (0, 0))).collect(),
+            warps: vec![],
+        }
+    }
+
+    fn shifted(&self, (dx, dy): Pos) -> Block {
+        let shift = |(x, y): Pos| (x + dx, y + dy);
+        Block {
+            cells: self
+                .cells
+                .iter()
+                .map(|(pos, cell)| (shift(*pos), cell.clone()))
+                .collect(),
+            width: self.width + dx,
+            height: self.height + dy,
+            right: shift(self.right),
+            down: shift(self.down),
+            ready: self.ready,
+            slots: self
+                .slots
+                .iter()
+                .map(|(name, pos)| (name.clone(), shift(*pos)))
+                .collect(),
+            warps: self
+                .warps
+                .iter()
+                .map(|(name, pos)| (name.clone(), shift(*pos)))
+                .collect(),
+        }
+    }
+
+    /// 左上を `(0, 0)` に寄せて、大きさをマスと出力を囲む範囲にする
+    fn normalized(&self) -> Block {
+        let positions = self
+            .cells
+            .iter()
+            .map(|(pos, _)| *pos)
+            .chain([self.right, self.down]);
+        let (x0, y0) = positions
+            .clone()
+            .fold((i64::MAX, i64::MAX), |(x0, y0), (x, y)| {
+                (x0.min(x), y0.min(y))
+            });
+        let (x1, y1) = positions.fold((i64::MIN, i64::MIN), |(x1, y1), (x, y)| {
+            (x1.max(x), y1.max(y))
+        });
+        let mut block = self.shifted((-x0, -y0));
+        block.width = x1 - x0 + 1;
+        block.height = y1 - y0 + 1;
+        block
+    }
+
+    fn area(&self) -> i64 {
+        self.width * self.height
+    }
+}
+
+/// 演算子を `chain` の順に斜めに並べ、`left` を左から、`up` を上から入れる
+///
+/// `stacked` なら `up` を `left` の上に、そうでなければ右上に置く。
+/// 最初の演算子は `min_fire` より前には動かない（上の入力の配線を延ばす）。
+fn combine(chain: &[Cell], left: &Block, up: &Block, stacked: bool, min_fire: i64) -> Block {
+    let even = |n: i64| n.rem_euclid(2) == 0;
+    let (lr, ud) = (left.right, up.down);
+    let (mut p, up_x) = if stacked {
+        let mut y = up.height + 1;
+        while !even(y + lr.1 - 1 - ud.1) {
+            y += 1;
+        }
+        let mut x = left.width.max(ud.0);
+        while !even(x - 1 - lr.0) {
+            x += 1;
+        }
+        ((x, y + lr.1), x - ud.0)
+    } else {
+        let mut up_x = left.width + 1;
+        while !even(up_x + ud.0 - 1 - lr.0) {
+            up_x += 1;
+        }
+        let mut y = lr.1.max(up.height);
+        while !even(y - 1 - ud.1) {
+            y += 1;
+        }
+        ((up_x + ud.0, y), up_x)
+    };
+    let arrive_left = left.ready + (p.0 - 1 - lr.0) / 2;
+    let mut arrive_up = up.ready + (p.1 - 1 - ud.1) / 2;
+    if arrive_left.max(arrive_up) < min_fire {
+        // 左の部品ごと下げて上の配線を延ばす
+        p.1 += 2 * (min_fire - arrive_up);
+        arrive_up = min_fire;
+    }
+    let fire = arrive_left.max(arrive_up);
+    let left = left.shifted((0, p.1 - lr.1));
+    let up = up.shifted((up_x, 0));
+
+    let mut cells = [left.cells, up.cells].concat();
+    for x in (left.right.0 + 1..p.0 - 1).step_by(2) {
+        cells.push(((x, p.1), Cell::Right));
+    }
+    for y in (up.down.1 + 1..p.1 - 1).step_by(2) {
+        cells.push(((p.0, y), Cell::Down));
+    }
+    let n = chain.len() as i64;
+    for (i, cell) in chain.iter().enumerate() {
+        cells.push(((p.0 + i as i64, p.1 + i as i64), cell.clone()));
+    }
+    let last = (p.0 + n - 1, p.1 + n - 1);
+    Block {
+        cells,
+        width: left.width.max(up.width).max(last.0 + 2),
+        height: left.height.max(up.height).max(last.1 + 2),
+        right: (last.0 + 1, last.1),
+        down: (last.0, last.1 + 1),
+        ready: fire + n,
+        slots: [left.slots, up.slots].concat(),
+        warps: vec![],
+    }
+}
+
+/// 割る数が 0 になりうるか
+fn may_be_zero(node: &Node) -> bool {
+    !matches!(node, Node::Leaf(Cell::Integer(n), None) if !n.is_zero())
+}
+
+/// 子から順に、面積が一番小さくなる向きと置き方を選ぶ
+///
+/// `div_fire` があれば、0 で割りうる `/` `%` はその時刻より前に動かさない。
+fn place(node: &Node, div_fire: Option<i64>) -> Block {
+    let (chain, left, up) = match node {
+        Node::Leaf(cell, slot) => return Block::leaf(cell.clone(), slot.clone()),
+        Node::Op(cell, left, up) => (vec![cell.clone()], left, up),
+        Node::Zero(gate, left, up) => {
+            let chain = if *gate == Cell::Eq {
+                vec![Cell::Eq, Cell::Sub]
+            } else {
+                vec![Cell::Ne, Cell::Sub, Cell::Mod]
+            };
+            (chain, left, up)
+        }
+    };
+    let symmetric = matches!(node, Node::Zero(..)) || matches!(chain[0], Cell::Add | Cell::Mul);
+    let min_fire = match div_fire {
+        Some(t) if matches!(chain[0], Cell::Div | Cell::Mod) && may_be_zero(up) => t,
+        _ => 0,
+    };
+    let (left, up) = (place(left, div_fire), place(up, div_fire));
+    let mut orders = vec![(&left, &up)];
+    if symmetric {
+        orders.push((&up, &left));
+    }
+    orders
+        .into_iter()
+        .flat_map(|(l, u)| [false, true].map(|stacked| combine(&chain, l, u, stacked, min_fire)))
+        .min_by_key(|block| (block.area(), block.ready, block.width + block.height))
+        .unwrap()
+}
+
+/// `tree` の出力を `m` 本に分けて、どれも時刻 `warp_tick` に `@` に届くように配線する
+///
+/// 幹を下（`down`）か右に延ばし、4 マスごとに枝を出す。枝が短いほど幹が長いので届く時刻は揃う。
+/// `@` の `dx` `dy` `dt` は後で埋める。
+fn rack(tree: &Block, name: &str, m: i64, warp_tick: i64, down: bool) -> Block {
+    let span = 2 * (warp_tick - tree.ready);
+    let (ex, ey) = if down { tree.down } else { tree.right };
+    // 下に延ばすときは (x, y) を入れ替えて考える
+    let at = |along: i64, across: i64| {
+        if down {
+            (ex + across, ey + along)
+        } else {
+            (ex + along, ey + across)
+        }
+    };
+    let (trunk, branch) = if down {
+        (Cell::Down, Cell::Right)
+    } else {
+        (Cell::Right, Cell::Down)
+    };
+    let mut block = tree.clone();
+    for k in 0..2 * (m - 1) {
+        block.cells.push((at(2 * k + 1, 0), trunk.clone()));
+    }
+    for j in 0..m {
+        let len = span - 4 * j;
+        for k in (1..len).step_by(2) {
+            block.cells.push((at(4 * j, k), branch.clone()));
+        }
+        let end = at(4 * j, len);
+        let warp = (end.0, end.1 + 1);
+        block.cells.extend([
+            (warp, Cell::Warp),
+            ((warp.0 - 1, warp.1), Cell::Integer(0.into())),
+            ((warp.0 + 1, warp.1), Cell::Integer(0.into())),
+            ((warp.0, warp.1 + 1), Cell::Integer(0.into())),
+        ]);
+        block.warps.push((name.to_owned(), warp));
+    }
+    block
+}
+
+/// 部品を棚に詰める（間は 1 マス空ける）。どの幅で折り返すかは全部試して面積が一番小さいもの
+fn pack(blocks: &[Block]) -> Vec<(Pos, &Block)> {
+    let mut order = blocks.iter().collect::<Vec<_>>();
+    order.sort_by_key(|block| (-block.height, -block.width));
+    let widest = order.iter().map(|block| block.width).max().unwrap_or(0);
+    let total = order.iter().map(|block| block.width + 1).sum::<i64>();
+    (widest..=total)
+        .map(|limit| {
+            let (mut x, mut y, mut shelf) = (0, 0, 0);
+            let mut width = 0;
+            let mut placed = vec![];
+            for block in &order {
+                if x > 0 && x + block.width > limit {
+                    (x, y, shelf) = (0, y + shelf + 1, 0);
+                }
+                placed.push(((x, y), *block));
+                width = width.max(x + block.width);
+                shelf = shelf.max(block.height);
+                x += block.width + 1;
+            }
+            ((width * (y + shelf), width + y + shelf), placed)
+        })
+        .min_by_key(|(key, _)| *key)
+        .map_or(vec![], |(_, placed)| placed)
+}
+
+/// マスを囲む範囲だけの盤面にする
+fn to_grid(cells: &BTreeMap<Pos, Cell>) -> Grid {
+    let x0 = cells.keys().map(|p| p.0).min().unwrap_or(0);
+    let x1 = cells.keys().map(|p| p.0).max().unwrap_or(0);
+    let y0 = cells.keys().map(|p| p.1).min().unwrap_or(0);
+    let y1 = cells.keys().map(|p| p.1).max().unwrap_or(0);
+    let rows = (y0..=y1)
+        .map(|y| {
+            (x0..=x1)
+                .map(|x| cells.get(&(x, y)).cloned().unwrap_or(Cell::Empty))
+                .collect()
+        })
+        .collect();
+    Grid { rows }
+}
+
+/// ループのない式の値を `S` に書く
+pub fn straight(node: &Node) -> anyhow::Result<Grid> {
+    let block = place(node, None);
+    let mut cells = block.cells.into_iter().collect::<BTreeMap<_, _>>();
+    if let Node::Leaf(..) = node {
+        cells.insert((1, 0), Cell::Right);
+        cells.insert((2, 0), Cell::Submit);
+    } else {
+        cells.insert(block.right, Cell::Submit);
+    }
+    Ok(to_grid(&cells))
+}
+
+/// `exit` の値が出たら `S` に書き、そうでなければ `updates` の値を変数のマスに書いて時刻 1 に戻る
+pub fn looped(updates: &[(String, Node)], exit: &Node) -> anyhow::Result<Grid> {
+    let mut exit = place(exit, None);
+    let submit_tick = exit.ready - 1;
+    exit.cells.push((exit.right, Cell::Submit));
+    // 抜ける周に 0 で割らないよう、`S` に書いた後で割る
+    let trees = updates
+        .iter()
+        .map(|(name, node)| (name, place(node, Some(submit_tick + 1))))
+        .collect::<Vec<_>>();
+
+    let mut uses = HashMap::<&str, i64>::new();
+    for block in std::iter::once(&exit).chain(trees.iter().map(|(_, block)| block)) {
+        for (name, _) in &block.slots {
+            *uses.entry(name).or_default() += 1;
+        }
+    }
+    let dt = trees
+        .iter()
+        .map(|(name, tree)| tree.ready + 2 * uses[name.as_str()] - 2)
+        .fold(submit_tick, i64::max);
+    if dt > 99 {
+        bail!("Loop body takes {dt} ticks, more than 99");
+    }
+    let mut blocks = vec![exit.normalized()];
+    for (name, tree) in &trees {
+        let m = uses[name.as_str()];
+        let block = [true, false]
+            .map(|down| rack(tree, name, m, dt + 1, down).normalized())
+            .into_iter()
+            .min_by_key(|block| (block.area(), block.width + block.height))
+            .unwrap();
+        blocks.push(block);
+    }
+
+    let mut cells = BTreeMap::new();
+    let mut slots = HashMap::<&str, Vec<Pos>>::new();
+    let mut warps = vec![];
+    let packed = pack(&blocks);
+    for &((x, y), block) in &packed {
+        for ((cx, cy), cell) in &block.cells {
+            cells.insert((x + cx, y + cy), cell.clone());
+        }
+        for (name, (sx, sy)) in &block.slots {
+            slots.entry(name).or_default().push((x + sx, y + sy));
+        }
+        for (name, (wx, wy)) in &block.warps {
+            warps.push((name.as_str(), (x + wx, y + wy)));
+        }
+    }
+    for (name, (wx, wy)) in warps {
+        let (sx, sy) = slots.get_mut(name).unwrap().pop().unwrap();
+        let (dx, dy) = (wx - sx, wy - sy);
+        if dx.abs() > 99 || dy.abs() > 99 {
+            bail!("Time warp for {name} is too far: ({dx}, {dy})");
+        }
+        cells.insert((wx - 1, wy), Cell::Integer(dx.into()));
+        cells.insert((wx + 1, wy), Cell::Integer(dy.into()));
+        cells.insert((wx, wy + 1), Cell::Integer(dt.into()));
+    }
+    Ok(to_grid(&cells))
+}
@@ -0,0 +1,240 @@
+use anyhow::{bail, Context};
+use num_bigint::BigInt;
+
+use super::{Loop, Op, Program, SourcePos, Stmt, Term};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Lexeme {
+    Int(BigInt),
+    Ident(String),
+    Symbol(&'static str),
+}
+
+/// 長いものから順に並べる
+const SYMBOLS: [&str; 12] = ["==", "!=", "=", "+", "-", "*", "/", "%", "(", ")", "{", "}"];
+
+const KEYWORDS: [&str; 5] = ["let", "while", "return", "A", "B"];
+
+fn is_ident_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '\''
+}
+
+/// 字句に分ける（`#` から行末まではコメント）
+fn lex(src: &str) -> anyhow::Result<Vec<(SourcePos, Lexeme)>> {
+    let mut lexemes = vec![];
+    for (i, line) in src.lines().enumerate() {
+        let chars = line.chars().collect::<Vec<_>>();
+        let mut j = 0;
+        while j < chars.len() {
+            let pos = SourcePos {
+                line: i + 1,
+                col: j + 1,
+            };
+            let c = chars[j];
+            if c.is_whitespace() {
+                j += 1;
+            } else if c == '#' {
+                break;
+            } else if c.is_ascii_digit() {
+                let len = chars[j..].iter().take_while(|c| c.is_ascii_digit()).count();
+                let digits = chars[j..j + len].iter().collect::<String>();
+                lexemes.push((pos, Lexeme::Int(digits.parse()?)));
+                j += len;
+            } else if c.is_ascii_alphabetic() || c == '_' {
+                let len = chars[j..].iter().take_while(|&&c| is_ident_char(c)).count();
+                let ident = chars[j..j + len].iter().collect::<String>();
+                lexemes.push((pos, Lexeme::Ident(ident)));
+                j += len;
+            } else {
+                let rest = chars[j..].iter().collect::<String>();
+                let symbol = SYMBOLS
+                    .iter()
+                    .find(|s| rest.starts_with(*s))
+                    .with_context(|| format!("Unexpected character at {pos}: {c}"))?;
+                lexemes.push((pos, Lexeme::Symbol(symbol)));
+                j += symbol.len();
+            }
+        }
+    }
+    Ok(lexemes)
+}
+
+struct Parser {
+    lexemes: Vec<(SourcePos, Lexeme)>,
+    pos: usize,
+    end: SourcePos,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Lexeme> {
+        self.lexemes.get(self.pos).map(|(_, l)| l)
+    }
+
+    /// 次の字句の位置（末尾なら最後の行の次）
+    fn here(&self) -> SourcePos {
+        self.lexemes.get(self.pos).map_or(self.end, |&(pos, _)| pos)
+    }
+
+    fn is_symbol(&self, symbol: &str) -> bool {
+        matches!(self.peek(), Some(Lexeme::Symbol(s)) if *s == symbol)
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Lexeme::Ident(s)) if s == keyword)
+    }
+
+    fn expect_symbol(&mut self, symbol: &str) -> anyhow::Result<()> {
+        if !self.is_symbol(symbol) {
+            bail!("Expected '{symbol}' at {}", self.here());
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> anyhow::Result<()> {
+        if !self.is_keyword(keyword) {
+            bail!("Expected '{keyword}' at {}", self.here());
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn ident(&mut self) -> anyhow::Result<String> {
+        match self.peek() {
+            Some(Lexeme::Ident(s)) if !KEYWORDS.contains(&s.as_str()) => {
+                let s = s.clone();
+                self.pos += 1;
+                Ok(s)
+            }
+            _ => bail!("Expected identifier at {}", self.here()),
+        }
+    }
+
+    /// `let x = e`
+    fn binding(&mut self) -> anyhow::Result<(String, Term)> {
+        self.expect_keyword("let")?;
+        let name = self.ident()?;
+        self.expect_symbol("=")?;
+        Ok((name, self.expr()?))
+    }
+
+    fn program(&mut self) -> anyhow::Result<Program> {
+        let mut lets = vec![];
+        while self.is_keyword("let") {
+            lets.push(self.binding()?);
+        }
+        let main_loop = if self.is_keyword("while") {
+            Some(self.main_loop()?)
+        } else {
+            None
+        };
+        self.expect_keyword("return")?;
+        let result = self.expr()?;
+        Ok(Program {
+            lets,
+            main_loop,
+            result,
+        })
+    }
+
+    fn main_loop(&mut self) -> anyhow::Result<Loop> {
+        self.expect_keyword("while")?;
+        let lhs = self.expr()?;
+        let eq = if self.is_symbol("==") {
+            true
+        } else if self.is_symbol("!=") {
+            false
+        } else {
+            bail!("Expected '==' or '!=' at {}", self.here());
+        };
+        self.pos += 1;
+        let rhs = self.expr()?;
+        self.expect_symbol("{")?;
+        let mut body = vec![];
+        while !self.is_symbol("}") {
+            if self.is_keyword("let") {
+                let (name, term) = self.binding()?;
+                body.push(Stmt::Let(name, term));
+            } else {
+                let pos = self.here();
+                let name = self.ident()?;
+                self.expect_symbol("=")?;
+                body.push(Stmt::Assign(name, self.expr()?, pos));
+            }
+        }
+        self.pos += 1;
+        Ok(Loop { lhs, eq, rhs, body })
+    }
+
+    /// 優先順位の低い順に `+ -` `* / %`
+    fn expr(&mut self) -> anyhow::Result<Term> {
+        self.binary(0)
+    }
+
+    fn binary(&mut self, level: usize) -> anyhow::Result<Term> {
+        const LEVELS: [&[(&str, Op)]; 2] = [
+            &[("+", Op::Add), ("-", Op::Sub)],
+            &[("*", Op::Mul), ("/", Op::Div), ("%", Op::Mod)],
+        ];
+        if level == LEVELS.len() {
+            return self.unary();
+        }
+        let mut lhs = self.binary(level + 1)?;
+        while let Some(&(_, op)) = LEVELS[level].iter().find(|(s, _)| self.is_symbol(s)) {
+            self.pos += 1;
+            let rhs = self.binary(level + 1)?;
+            lhs = Term::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    /// `-e` は `0 - e`
+    fn unary(&mut self) -> anyhow::Result<Term> {
+        if self.is_symbol("-") {
+            self.pos += 1;
+            let term = self.unary()?;
+            return Ok(Term::Binary(
+                Op::Sub,
+                Box::new(Term::Int(0.into())),
+                Box::new(term),
+            ));
+        }
+        self.atom()
+    }
+
+    fn atom(&mut self) -> anyhow::Result<Term> {
+        let pos = self.here();
+        let term = match self.peek().cloned() {
+            Some(Lexeme::Int(i)) => Term::Int(i),
+            Some(Lexeme::Ident(s)) if s == "A" => Term::A,
+            Some(Lexeme::Ident(s)) if s == "B" => Term::B,
+            Some(Lexeme::Ident(_)) => return Ok(Term::Var(self.ident()?, pos)),
+            Some(Lexeme::Symbol("(")) => {
+                self.pos += 1;
+                let term = self.expr()?;
+                self.expect_symbol(")")?;
+                return Ok(term);
+            }
+            Some(l) => bail!("Unexpected {l:?} at {pos}"),
+            None => bail!("Unexpected end of input at {pos}"),
+        };
+        self.pos += 1;
+        Ok(term)
+    }
+}
+
+pub fn parse(src: &str) -> anyhow::Result<Program> {
+    let mut parser = Parser {
+        lexemes: lex(src)?,
+        pos: 0,
+        end: SourcePos {
+            line: src.lines().count() + 1,
+            col: 1,
+        },
+    };
+    let program = parser.program()?;
+    if parser.peek().is_some() {
+        bail!("Unexpected input at {}", parser.here());
+    }
+    Ok(program)
+}
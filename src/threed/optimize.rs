@@ -0,0 +1,300 @@
+//! 3D 言語の盤面を局所探索で小さくする
+//!
+//! 盤面を少しずつ変えて（行や列を消す・マスを消す・隣の行や列と入れ替える・マスを隣に動かす）、
+//! 課題の例を全部通して時空間の体積が真に小さくなったものだけ残す。
+//! 例だけでは壊れた盤面も通るので、0 や 1 と乱数の入力でも元の盤面と同じ答えになるか確かめる
+//! （元の盤面が止まらない入力は使わない）。
+//! 行や列を動かすときは、`@` の `dx` `dy` が整数なら同じマスに飛ぶように書き直す。
+
+use std::fmt;
+
+use num_bigint::BigInt;
+
+use super::{validate, Cell, Example, Grid, Score, Simulator};
+
+/// 盤面の変形（位置は `(x, y)`）
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Move {
+    DeleteRow(usize),
+    DeleteColumn(usize),
+    Remove((usize, usize)),
+    /// `y` 行目と `y + 1` 行目
+    SwapRows(usize),
+    /// `x` 列目と `x + 1` 列目
+    SwapColumns(usize),
+    /// 空きのマスへ動かす
+    Shift((usize, usize), (usize, usize)),
+}
+
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Move::DeleteRow(y) => write!(f, "delete row {y}"),
+            Move::DeleteColumn(x) => write!(f, "delete column {x}"),
+            Move::Remove((x, y)) => write!(f, "remove ({x}, {y})"),
+            Move::SwapRows(y) => write!(f, "swap rows {y} and {}", y + 1),
+            Move::SwapColumns(x) => write!(f, "swap columns {x} and {}", x + 1),
+            Move::Shift((x, y), (to_x, to_y)) => write!(f, "move ({x}, {y}) to ({to_x}, {to_y})"),
+        }
+    }
+}
+
+impl Move {
+    /// 変形した盤面（できなければ `None`）
+    pub fn apply(self, grid: &Grid) -> Option<Grid> {
+        let (width, height) = (grid.width(), grid.height());
+        let in_range = match self {
+            Move::DeleteRow(y) => y < height,
+            Move::DeleteColumn(x) => x < width,
+            Move::SwapRows(y) => y + 1 < height,
+            Move::SwapColumns(x) => x + 1 < width,
+            Move::Remove(_) | Move::Shift(..) => true,
+        };
+        if !in_range {
+            return None;
+        }
+        match self {
+            Move::DeleteRow(y) => remap(grid, (width, height - 1), |(px, py)| match py {
+                _ if py == y => None,
+                _ if py > y => Some((px, py - 1)),
+                _ => Some((px, py)),
+            }),
+            Move::DeleteColumn(x) => remap(grid, (width - 1, height), |(px, py)| match px {
+                _ if px == x => None,
+                _ if px > x => Some((px - 1, py)),
+                _ => Some((px, py)),
+            }),
+            Move::SwapRows(y) => remap(grid, (width, height), |(px, py)| match py {
+                _ if py == y => Some((px, y + 1)),
+                _ if py == y + 1 => Some((px, y)),
+                _ => Some((px, py)),
+            }),
+            Move::SwapColumns(x) => remap(grid, (width, height), |(px, py)| match px {
+                _ if px == x => Some((x + 1, py)),
+                _ if px == x + 1 => Some((x, py)),
+                _ => Some((px, py)),
+            }),
+            Move::Remove((x, y)) => {
+                let mut grid = grid.clone();
+                *grid.rows.get_mut(y)?.get_mut(x)? = Cell::Empty;
+                Some(grid)
+            }
+            Move::Shift((x, y), (to_x, to_y)) => {
+                let mut grid = grid.clone();
+                if grid.get(to_x, to_y)? != &Cell::Empty {
+                    return None;
+                }
+                let cell = std::mem::replace(grid.rows.get_mut(y)?.get_mut(x)?, Cell::Empty);
+                grid.rows[to_y][to_x] = cell;
+                Some(grid)
+            }
+        }
+    }
+}
+
+/// マスを `map` で動かした `size` の盤面にする（`None` のマスは消す）
+///
+/// `@` と飛ぶ先が両方残れば、`dx` `dy` を動かした後の位置に合わせる。
+fn remap(
+    grid: &Grid,
+    (width, height): (usize, usize),
+    map: impl Fn((usize, usize)) -> Option<(usize, usize)>,
+) -> Option<Grid> {
+    let mut rows = vec![vec![Cell::Empty; width]; height];
+    for ((x, y), cell) in grid.cells() {
+        if let Some((to_x, to_y)) = map((x, y)) {
+            rows[to_y][to_x] = cell.clone();
+        }
+    }
+    let integer = |x: usize, y: usize| match grid.get(x, y) {
+        Some(Cell::Integer(n)) => i64::try_from(n).ok(),
+        _ => None,
+    };
+    for ((x, y), cell) in grid.cells() {
+        if *cell != Cell::Warp || x == 0 {
+            continue;
+        }
+        let (Some(dx), Some(dy)) = (integer(x - 1, y), integer(x + 1, y)) else {
+            continue;
+        };
+        let (target_x, target_y) = (x as i64 - dx, y as i64 - dy);
+        if target_x < 0 || target_y < 0 {
+            continue;
+        }
+        let (Some(warp), Some(target)) = (map((x, y)), map((target_x as usize, target_y as usize)))
+        else {
+            continue;
+        };
+        // `dx` `dy` のマスが `@` の隣のまま動いたときだけ直す
+        if warp.0 == 0
+            || map((x - 1, y)) != Some((warp.0 - 1, warp.1))
+            || map((x + 1, y)) != Some((warp.0 + 1, warp.1))
+        {
+            continue;
+        }
+        let dx = warp.0 as i64 - target.0 as i64;
+        let dy = warp.1 as i64 - target.1 as i64;
+        if dx.abs() > 99 || dy.abs() > 99 {
+            return None;
+        }
+        rows[warp.1][warp.0 - 1] = Cell::Integer(dx.into());
+        rows[warp.1][warp.0 + 1] = Cell::Integer(dy.into());
+    }
+    Some(Grid { rows })
+}
+
+/// 空きだけの外側の行と列を除き、行の長さを揃える（位置がずれても `@` の飛ぶ先は相対なので変わらない）
+pub fn trim(grid: &Grid) -> Grid {
+    let cells = grid.cells().map(|(pos, _)| pos).collect::<Vec<_>>();
+    let x0 = cells.iter().map(|p| p.0).min().unwrap_or(0);
+    let y0 = cells.iter().map(|p| p.1).min().unwrap_or(0);
+    let x1 = cells.iter().map(|p| p.0 + 1).max().unwrap_or(0);
+    let y1 = cells.iter().map(|p| p.1 + 1).max().unwrap_or(0);
+    let rows = (y0..y1)
+        .map(|y| {
+            (x0..x1)
+                .map(|x| grid.get(x, y).cloned().unwrap_or(Cell::Empty))
+                .collect()
+        })
+        .collect();
+    Grid { rows }
+}
+
+/// 試す変形を効きそうな順に
+pub fn moves(grid: &Grid) -> Vec<Move> {
+    let (width, height) = (grid.width(), grid.height());
+    let mut moves = vec![];
+    moves.extend((0..height).map(Move::DeleteRow));
+    moves.extend((0..width).map(Move::DeleteColumn));
+    for ((x, y), cell) in grid.cells() {
+        if *cell != Cell::Submit {
+            moves.push(Move::Remove((x, y)));
+        }
+    }
+    moves.extend((0..height.saturating_sub(1)).map(Move::SwapRows));
+    moves.extend((0..width.saturating_sub(1)).map(Move::SwapColumns));
+    for ((x, y), _) in grid.cells() {
+        let neighbors = [
+            (x.wrapping_sub(1), y),
+            (x + 1, y),
+            (x, y.wrapping_sub(1)),
+            (x, y + 1),
+        ];
+        for to in neighbors {
+            if grid.get(to.0, to.1) == Some(&Cell::Empty) {
+                moves.push(Move::Shift((x, y), to));
+            }
+        }
+    }
+    moves
+}
+
+/// 局所探索の結果
+#[derive(Clone, Debug)]
+pub struct Optimized {
+    pub grid: Grid,
+    pub score: Score,
+    /// 採用した変形と、そのときの体積
+    pub moves: Vec<(Move, u64)>,
+    /// 例で確かめた候補の数
+    pub evaluations: usize,
+}
+
+/// 乱数で作る入力の数
+const RANDOM_INPUTS: usize = 16;
+
+/// 例のほかに確かめる入力と、元の盤面での答えと実行した tick 数
+///
+/// `A` `B` は 0 と 1 の組み合わせと、例の値の大きさ（10 以上）までの乱数。
+fn extra_inputs(grid: &Grid, examples: &[Example], max_steps: usize) -> Vec<(Example, usize)> {
+    let bound = examples
+        .iter()
+        .flat_map(|example| [&example.a, &example.b])
+        .filter_map(|n| i64::try_from(n.magnitude()).ok())
+        .max()
+        .unwrap_or(0)
+        .clamp(10, 1000);
+    let mut x = 1u64;
+    let mut random = || {
+        x = x
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        (x >> 33) as i64 % (2 * bound + 1) - bound
+    };
+    let mut inputs = vec![(0, 0), (0, 1), (1, 0), (1, 1)];
+    inputs.extend((0..RANDOM_INPUTS).map(|_| (random(), random())));
+    inputs
+        .into_iter()
+        .filter_map(|(a, b)| {
+            let (a, b) = (BigInt::from(a), BigInt::from(b));
+            let outcome = Simulator::new(grid, &a, &b)
+                .with_max_steps(max_steps)
+                .run()
+                .ok()?;
+            let example = Example {
+                a,
+                b,
+                answer: outcome.answer,
+            };
+            Some((example, outcome.steps))
+        })
+        .collect()
+}
+
+/// 良くなる変形がなくなるか、`max_evaluations` 個の候補を確かめるまで盤面を小さくする
+///
+/// 候補の実行は元の盤面の tick 数の 2 倍で打ち切る（止まらない候補が多いので）。
+pub fn optimize(
+    grid: &Grid,
+    examples: &[Example],
+    max_evaluations: usize,
+) -> anyhow::Result<Optimized> {
+    let mut best = trim(grid);
+    let mut score = validate(&best, examples, super::MAX_STEPS)?;
+    let mut max_steps = 0;
+    for example in examples {
+        let outcome = Simulator::new(&best, &example.a, &example.b).run()?;
+        max_steps = max_steps.max(outcome.steps);
+    }
+    let extras = extra_inputs(&best, examples, 10 * max_steps + 1000);
+    let max_steps = 2 * max_steps + 100;
+    let agrees = |candidate: &Grid| {
+        extras.iter().all(|(example, steps)| {
+            Simulator::new(candidate, &example.a, &example.b)
+                .with_max_steps(2 * steps + 100)
+                .run()
+                .is_ok_and(|outcome| outcome.answer == example.answer)
+        })
+    };
+
+    let mut accepted = vec![];
+    let mut evaluations = 0;
+    let mut index = 0;
+    let mut since_improvement = 0;
+    let mut candidates = moves(&best);
+    while since_improvement < candidates.len() && evaluations < max_evaluations {
+        let m = candidates[index % candidates.len()];
+        index += 1;
+        since_improvement += 1;
+        let Some(candidate) = m.apply(&best).map(|grid| trim(&grid)) else {
+            continue;
+        };
+        evaluations += 1;
+        let Ok(candidate_score) = validate(&candidate, examples, max_steps) else {
+            continue;
+        };
+        if candidate_score.volume < score.volume && agrees(&candidate) {
+            accepted.push((m, candidate_score.volume));
+            best = candidate;
+            score = candidate_score;
+            candidates = moves(&best);
+            since_improvement = 0;
+        }
+    }
+    Ok(Optimized {
+        grid: best,
+        score,
+        moves: accepted,
+        evaluations,
+    })
+}
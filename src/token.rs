@@ -1,14 +1,22 @@
-use std::{cmp::min, collections::HashSet, str::FromStr};
+use std::{cmp::min, str::FromStr};
 
 use anyhow::{bail, Context};
+use indicatif::ProgressBar;
 use itertools::Itertools;
 use num_bigint::BigInt;
 
+use crate::ast::{
+    builder::{app, concat, div, eq, fix, int, ite, lam, let_in, rem, string, sub},
+    Expr,
+};
 pub use binary_op::BinaryOp;
 pub use unary_op::UnaryOp;
 
 mod binary_op;
+mod grammar;
 pub mod integers;
+pub mod random_walk;
+mod spaceship;
 pub mod strings;
 mod unary_op;
 
@@ -76,17 +84,25 @@ impl FromStr for Token {
 }
 
 pub fn encode(tokens: &[Token]) -> anyhow::Result<String> {
+    encode_with_progress(tokens, &ProgressBar::hidden())
+}
+
+/// 整数を変換した桁数を `bar` に表示しながらエンコードする（詰めた Lambdaman の移動列は大きい）
+pub fn encode_with_progress(tokens: &[Token], bar: &ProgressBar) -> anyhow::Result<String> {
     Ok(tokens
         .iter()
-        .map(encode_token)
+        .map(|token| encode_token(token, bar))
         .collect::<Result<Vec<_>, _>>()?
         .join(" "))
 }
 
-fn encode_token(token: &Token) -> anyhow::Result<String> {
+fn encode_token(token: &Token, bar: &ProgressBar) -> anyhow::Result<String> {
     match token {
-        Token::Boolean(_) => todo!(),
-        Token::Integer(v) => Ok(format!("I{}", integers::encode(v.clone())?)),
+        Token::Boolean(b) => Ok(if *b { "T" } else { "F" }.to_owned()),
+        Token::Integer(v) => Ok(format!(
+            "I{}",
+            integers::encode_with_progress(v.clone(), bar)?
+        )),
         Token::String(s) => Ok(format!("S{}", strings::encode(s)?)),
         Token::UnaryOp(op) => Ok(format!("U{}", unary_op::encode(op)?)),
         Token::BinaryOp(op) => Ok(format!("B{}", binary_op::encode(op)?)),
@@ -96,208 +112,131 @@ fn encode_token(token: &Token) -> anyhow::Result<String> {
     }
 }
 
+/// n % order.len() 番目の文字 `? B= B% n k I! S.. ? B= B% n k I" S.. ... S..`
+fn digit_to_char(n: &Expr, order: &[char]) -> Expr {
+    let (last, init) = order.split_last().expect("order is not empty");
+    init.iter()
+        .enumerate()
+        .rev()
+        .fold(string(last.to_string()), |otherwise, (i, c)| {
+            ite(
+                eq(rem(n.clone(), int(order.len())), int(i)),
+                string(c.to_string()),
+                otherwise,
+            )
+        })
+}
+
 pub fn encode_string(s: &str) -> anyhow::Result<Vec<Token>> {
     let mut current = vec![Token::String(s.to_owned())];
     let mut min_len = encode(&current[..]).unwrap().len();
-    if s.split_whitespace()
-        .last()
-        .unwrap()
-        .chars()
-        .all(|c| ['D', 'L', 'R', 'U'].contains(&c))
-    {
+    // 最後の単語が経路で文字列の末尾にあるとき（後ろに空白があると経路の後に付け足せない）
+    let path = s.split_whitespace().last().filter(|path| {
+        s.ends_with(path) && path.chars().all(|c| ['D', 'L', 'R', 'U'].contains(&c))
+    });
+    if let Some(path) = path {
         // lambdaman 用のエンコード
-        let len = s.split_whitespace().last().unwrap().len();
-        let first_char = s.split_whitespace().last().unwrap().chars().next().unwrap();
+        let head = &s[..s.len() - path.len()];
+        let first_char = path.chars().next().unwrap();
         // D L R U のうち登場するもの（0 を終端とするため最初の文字を最後に持っていく）
-        let mut order = s
-            .split_whitespace()
-            .last()
-            .unwrap()
+        // 出力が実行ごとに変わらないように文字の順で並べる
+        let mut order = path
             .chars()
-            .collect::<HashSet<_>>()
-            .iter()
-            .sorted_by_key(|c| **c == first_char)
-            .copied()
+            .unique()
+            .sorted_by_key(|&c| (c == first_char, c))
             .collect_vec();
         if order.len() == 1 {
             order.insert(0, ' ');
         }
-        let mut cand = vec![
-            Token::BinaryOp(BinaryOp::Concat),
-            Token::String(s[..s.len() - len].to_owned()),
-            Token::BinaryOp(BinaryOp::Apply),
-            Token::BinaryOp(BinaryOp::Apply),
-            Token::Lambda(BigInt::from(1)),
-            Token::BinaryOp(BinaryOp::Apply),
-            Token::Lambda(BigInt::from(2)),
-            Token::BinaryOp(BinaryOp::Apply),
-            Token::Variable(BigInt::from(1)),
-            Token::BinaryOp(BinaryOp::Apply),
-            Token::Variable(BigInt::from(2)),
-            Token::Variable(BigInt::from(2)),
-            Token::Lambda(BigInt::from(2)),
-            Token::BinaryOp(BinaryOp::Apply),
-            Token::Variable(BigInt::from(1)),
-            Token::BinaryOp(BinaryOp::Apply),
-            Token::Variable(BigInt::from(2)),
-            Token::Variable(BigInt::from(2)),
-            Token::Lambda(BigInt::from(3)),
-            Token::Lambda(BigInt::from(4)),
-            Token::If,
-            Token::BinaryOp(BinaryOp::Equal),
-            Token::Variable(BigInt::from(4)),
-            Token::Integer(BigInt::from(0)),
-            Token::String("".to_owned()),
-            Token::BinaryOp(BinaryOp::Concat),
-            Token::BinaryOp(BinaryOp::Apply),
-            Token::Variable(BigInt::from(3)),
-            Token::BinaryOp(BinaryOp::Div),
-            Token::Variable(BigInt::from(4)),
-            Token::Integer(BigInt::from(order.len())),
-        ];
-        cand.extend(order.iter().enumerate().flat_map(|(i, c)| {
-            if i != order.len() - 1 {
-                vec![
-                    Token::If,
-                    Token::BinaryOp(BinaryOp::Equal),
-                    Token::BinaryOp(BinaryOp::Mod),
-                    Token::Variable(BigInt::from(4)),
-                    Token::Integer(BigInt::from(order.len())),
-                    Token::Integer(BigInt::from(i)),
-                    Token::String(c.to_string()),
-                ]
-            } else {
-                vec![Token::String(c.to_string())]
-            }
-        }));
-        cand.extend(vec![Token::Integer(
-            s.split_whitespace()
-                .last()
-                .unwrap()
-                .chars()
-                .fold(BigInt::from(0), |acc, c| {
-                    acc * order.len() + order.iter().position(|m| c == *m).unwrap()
+        let prefix = string(head);
+        // n を order.len() 進数として下の桁から文字に直す
+        let cand = concat(
+            prefix.clone(),
+            app(
+                fix(|f| {
+                    lam(|n| {
+                        ite(
+                            eq(n.clone(), int(0)),
+                            string(""),
+                            concat(
+                                app(f, div(n.clone(), int(order.len()))),
+                                digit_to_char(&n, &order),
+                            ),
+                        )
+                    })
                 }),
-        )]);
+                int(path.chars().fold(BigInt::from(0), |acc, c| {
+                    acc * order.len() + order.iter().position(|m| c == *m).unwrap()
+                })),
+            ),
+        )
+        .to_tokens();
+        let cand_len = encode(&cand[..]).unwrap().len();
+        if cand_len < min_len {
+            min_len = cand_len;
+            current = cand;
+        }
+        let cand = grammar::encode(head, path);
         let cand_len = encode(&cand[..]).unwrap().len();
         if cand_len < min_len {
             min_len = cand_len;
             current = cand;
         }
-        let len_group = s
-            .split_whitespace()
-            .last()
-            .unwrap()
+        let len_group = path
             .chars()
             .chunk_by(|c| *c)
             .into_iter()
             .map(|(_, chunk)| chunk.count())
-            .collect::<HashSet<_>>();
+            .sorted()
+            .dedup()
+            .collect_vec();
         for len_current in len_group {
-            let mut cand = vec![
-                Token::BinaryOp(BinaryOp::Concat),
-                Token::String(s[..s.len() - len].to_owned()),
-                Token::BinaryOp(BinaryOp::Apply),
-                Token::BinaryOp(BinaryOp::Apply),
-                Token::Lambda(BigInt::from(1)),
-                Token::BinaryOp(BinaryOp::Apply),
-                Token::Lambda(BigInt::from(2)),
-                Token::BinaryOp(BinaryOp::Apply),
-                Token::Variable(BigInt::from(1)),
-                Token::BinaryOp(BinaryOp::Apply),
-                Token::Variable(BigInt::from(2)),
-                Token::Variable(BigInt::from(2)),
-                Token::Lambda(BigInt::from(2)),
-                Token::BinaryOp(BinaryOp::Apply),
-                Token::Variable(BigInt::from(1)),
-                Token::BinaryOp(BinaryOp::Apply),
-                Token::Variable(BigInt::from(2)),
-                Token::Variable(BigInt::from(2)),
-                Token::Lambda(BigInt::from(3)),
-                Token::Lambda(BigInt::from(4)),
-                Token::If,
-                Token::BinaryOp(BinaryOp::Equal),
-                Token::Variable(BigInt::from(4)),
-                Token::Integer(BigInt::from(0)),
-                Token::String("".to_owned()),
-                Token::BinaryOp(BinaryOp::Concat),
-                Token::BinaryOp(BinaryOp::Apply),
-                Token::Variable(BigInt::from(3)),
-                Token::BinaryOp(BinaryOp::Div),
-                Token::Variable(BigInt::from(4)),
-                Token::Integer(BigInt::from(order.len() * len_current)),
-                Token::BinaryOp(BinaryOp::Apply),
-                Token::Lambda(BigInt::from(9)),
-                Token::BinaryOp(BinaryOp::Apply),
-                Token::BinaryOp(BinaryOp::Apply),
-                Token::Lambda(BigInt::from(5)),
-                Token::BinaryOp(BinaryOp::Apply),
-                Token::Lambda(BigInt::from(6)),
-                Token::BinaryOp(BinaryOp::Apply),
-                Token::Variable(BigInt::from(5)),
-                Token::BinaryOp(BinaryOp::Apply),
-                Token::Variable(BigInt::from(6)),
-                Token::Variable(BigInt::from(6)),
-                Token::Lambda(BigInt::from(6)),
-                Token::BinaryOp(BinaryOp::Apply),
-                Token::Variable(BigInt::from(5)),
-                Token::BinaryOp(BinaryOp::Apply),
-                Token::Variable(BigInt::from(6)),
-                Token::Variable(BigInt::from(6)),
-                Token::Lambda(BigInt::from(7)),
-                Token::Lambda(BigInt::from(8)),
-                Token::If,
-                Token::BinaryOp(BinaryOp::Equal),
-                Token::Variable(BigInt::from(8)),
-                Token::Integer(BigInt::from(0)),
-                Token::Variable(BigInt::from(9)),
-                Token::BinaryOp(BinaryOp::Concat),
-                Token::Variable(BigInt::from(9)),
-                Token::BinaryOp(BinaryOp::Apply),
-                Token::Variable(BigInt::from(7)),
-                Token::BinaryOp(BinaryOp::Sub),
-                Token::Variable(BigInt::from(8)),
-                Token::Integer(BigInt::from(1)),
-                Token::BinaryOp(BinaryOp::Mod),
-                Token::BinaryOp(BinaryOp::Div),
-                Token::Variable(BigInt::from(4)),
-                Token::Integer(BigInt::from(order.len())),
-                Token::Integer(BigInt::from(len_current)),
-            ];
-            cand.extend(order.iter().enumerate().flat_map(|(i, c)| {
-                if i != order.len() - 1 {
-                    vec![
-                        Token::If,
-                        Token::BinaryOp(BinaryOp::Equal),
-                        Token::BinaryOp(BinaryOp::Mod),
-                        Token::Variable(BigInt::from(4)),
-                        Token::Integer(BigInt::from(order.len())),
-                        Token::Integer(BigInt::from(i)),
-                        Token::String(c.to_string()),
-                    ]
-                } else {
-                    vec![Token::String(c.to_string())]
-                }
-            }));
-            cand.extend(vec![Token::Integer(
-                s.split_whitespace()
-                    .last()
-                    .unwrap()
-                    .chars()
-                    .chunk_by(|c| *c)
-                    .into_iter()
-                    .fold(BigInt::from(0), |acc, (c, g)| {
-                        let mut res = acc;
-                        let mut remain = g.count();
-                        while remain > 0 {
-                            res = res * order.len() * len_current
-                                + (min(remain, len_current) - 1) * order.len()
-                                + order.iter().position(|m| c == *m).unwrap();
-                            remain -= min(remain, len_current);
-                        }
-                        res
+            // 下の桁から (文字, 連続数 - 1) を order.len() * len_current 進数で 1 桁にする
+            let cand = concat(
+                prefix.clone(),
+                app(
+                    fix(|f| {
+                        lam(|n| {
+                            ite(
+                                eq(n.clone(), int(0)),
+                                string(""),
+                                concat(
+                                    app(f, div(n.clone(), int(order.len() * len_current))),
+                                    let_in(digit_to_char(&n, &order), |c| {
+                                        app(
+                                            fix(|r| {
+                                                lam(|m| {
+                                                    ite(
+                                                        eq(m.clone(), int(0)),
+                                                        c.clone(),
+                                                        concat(c, app(r, sub(m, int(1)))),
+                                                    )
+                                                })
+                                            }),
+                                            rem(div(n, int(order.len())), int(len_current)),
+                                        )
+                                    }),
+                                ),
+                            )
+                        })
                     }),
-            )]);
+                    int(path.chars().chunk_by(|c| *c).into_iter().fold(
+                        BigInt::from(0),
+                        |acc, (c, g)| {
+                            let mut res = acc;
+                            let mut remain = g.count();
+                            while remain > 0 {
+                                res = res * order.len() * len_current
+                                    + (min(remain, len_current) - 1) * order.len()
+                                    + order.iter().position(|m| c == *m).unwrap();
+                                remain -= min(remain, len_current);
+                            }
+                            res
+                        },
+                    )),
+                ),
+            )
+            .to_tokens();
             let cand_len = encode(&cand[..]).unwrap().len();
             if cand_len < min_len {
                 min_len = cand_len;
@@ -305,105 +244,17 @@ pub fn encode_string(s: &str) -> anyhow::Result<Vec<Token>> {
             }
         }
     }
-    // if s.split_whitespace()
-    //     .last()
-    //     .unwrap()
-    //     .chars()
-    //     .all(|c| c.is_numeric())
-    // {
-    //     // spaceship 用のエンコード
-    //     let tokens = s
-    //         .chars()
-    //         .scan(false, |cum, c| {
-    //             let next = c.is_numeric() && (*cum || c != '0');
-    //             *cum = next;
-    //             Some((c, next))
-    //         })
-    //         .enumerate()
-    //         .chunk_by(|(_, (_, b))| *b)
-    //         .into_iter()
-    //         .map(|(key, chunk)| {
-    //             let chunk_array = chunk.collect::<Vec<_>>();
-    //             // 先頭と末尾では "B. " が不要になるので 8 桁分ボーナス
-    //             let len = chunk_array.len()
-    //                 + if chunk_array[0].0 == 0 { 8 } else { 0 }
-    //                 + if chunk_array[chunk_array.len() - 1].0 == s.len() - 1 {
-    //                     8
-    //                 } else {
-    //                     0
-    //                 };
-    //             // "B. B. S{} U$ I{} S{}" とエンコードするので増えた 13 文字以上の改善が得られる 27 桁以上連続しない場合は数値にしない
-    //             (
-    //                 chunk_array
-    //                     .into_iter()
-    //                     .map(|(_, (c, _))| c)
-    //                     .collect::<String>(),
-    //                 key && len >= 27,
-    //             )
-    //         })
-    //         .chunk_by(|(_, i)| *i)
-    //         .into_iter()
-    //         .map(|(key, chunk)| {
-    //             if key {
-    //                 vec![
-    //                     Token::BinaryOp(BinaryOp::Concat),
-    //                     Token::UnaryOp(UnaryOp::ToString),
-    //                     Token::BinaryOp(BinaryOp::Apply),
-    //                     Token::BinaryOp(BinaryOp::Apply),
-    //                     Token::Lambda(BigInt::from(1)),
-    //                     Token::BinaryOp(BinaryOp::Apply),
-    //                     Token::Lambda(BigInt::from(2)),
-    //                     Token::BinaryOp(BinaryOp::Apply),
-    //                     Token::Variable(BigInt::from(1)),
-    //                     Token::BinaryOp(BinaryOp::Apply),
-    //                     Token::Variable(BigInt::from(2)),
-    //                     Token::Variable(BigInt::from(2)),
-    //                     Token::Lambda(BigInt::from(2)),
-    //                     Token::BinaryOp(BinaryOp::Apply),
-    //                     Token::Variable(BigInt::from(1)),
-    //                     Token::BinaryOp(BinaryOp::Apply),
-    //                     Token::Variable(BigInt::from(2)),
-    //                     Token::Variable(BigInt::from(2)),
-    //                     Token::Lambda(BigInt::from(3)),
-    //                     Token::Lambda(BigInt::from(4)),
-    //                     Token::If,
-    //                     Token::BinaryOp(BinaryOp::Equal),
-    //                     Token::Variable(BigInt::from(4)),
-    //                     Token::Integer(BigInt::from(0)),
-    //                     Token::Integer(BigInt::from(0)),
-    //                     Token::BinaryOp(BinaryOp::Add),
-    //                     Token::BinaryOp(BinaryOp::Add),
-    //                     Token::BinaryOp(BinaryOp::Mod),
-    //                     Token::Variable(BigInt::from(4)),
-    //                     Token::Integer(BigInt::from(10)),
-    //                     Token::Integer(BigInt::from(52)),
-    //                     Token::BinaryOp(BinaryOp::Mul),
-    //                     Token::BinaryOp(BinaryOp::Apply),
-    //                     Token::Variable(BigInt::from(3)),
-    //                     Token::BinaryOp(BinaryOp::Div),
-    //                     Token::Variable(BigInt::from(4)),
-    //                     Token::Integer(BigInt::from(10)),
-    //                     Token::Integer(BigInt::from(94)),
-    //                     Token::Integer(
-    //                         BigInt::from_str(chunk.map(|(s, _)| s).join("").as_str()).unwrap(),
-    //                     ),
-    //                 ]
-    //             } else {
-    //                 vec![
-    //                     Token::BinaryOp(BinaryOp::Concat),
-    //                     Token::String(chunk.map(|(s, _)| s).join("")),
-    //                 ]
-    //             }
-    //         })
-    //         .collect_vec();
-    //     let len = tokens.len();
-    //     let cand = tokens
-    //         .into_iter()
-    //         .enumerate()
-    //         .flat_map(|(index, iter)| iter.into_iter().skip(if index != len - 1 { 0 } else { 1 }))
-    //         .collect_vec();
-    //     current = cand;
-    // }
+    if s.split_whitespace()
+        .last()
+        .is_some_and(|last| last.chars().all(|c| ('1'..='9').contains(&c)))
+    {
+        // spaceship 用のエンコード
+        let cand = spaceship::encode(s);
+        let cand_len = encode(&cand[..]).unwrap().len();
+        if cand_len < min_len {
+            current = cand;
+        }
+    }
     Ok(current)
 }
 
@@ -470,6 +321,36 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn encode_decode_large_integers() -> anyhow::Result<()> {
+        // 1 桁ずつ変換する素朴な実装と比べる
+        fn naive(mut value: BigInt) -> String {
+            let mut digits = vec![];
+            while value > BigInt::ZERO {
+                digits.push((u8::try_from(&value % 94).unwrap() + b'!') as char);
+                value /= 94;
+            }
+            if digits.is_empty() {
+                digits.push('!');
+            }
+            digits.into_iter().rev().collect()
+        }
+        let mut values = vec![BigInt::ZERO, BigInt::from(93), BigInt::from(94)];
+        for exp in [9, 10, 18, 19, 36, 37, 200, 1000] {
+            let power = BigInt::from(94).pow(exp);
+            values.extend([&power - 1, power.clone(), &power + 1]);
+        }
+        values.push(BigInt::from(7).pow(5000) * BigInt::from(3).pow(3000) + 12345);
+        for value in values {
+            let encoded = integers::encode(value.clone())?;
+            assert_eq!(encoded, naive(value.clone()));
+            assert_eq!(integers::decode(encoded.bytes())?, value);
+        }
+        assert_eq!(integers::decode(b"!!!\"".iter().copied())?, BigInt::from(1));
+        assert!(integers::encode(BigInt::from(-1)).is_err());
+        Ok(())
+    }
+
     #[test]
     fn encode_decode_hello_world() -> anyhow::Result<()> {
         assert_eq!(
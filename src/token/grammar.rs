@@ -0,0 +1,225 @@
+use super::Token;
+use crate::ast::{
+    builder::{call, concat, int, let_in, string},
+    stdlib::repeat,
+    Expr,
+};
+
+/// 繰り返しとして探す周期の上限
+const MAX_PERIOD: usize = 1000;
+
+/// `B$ B$ v" ` と `I` と区切りの空白
+const REPEAT_OVERHEAD: usize = 11;
+
+/// 文字列の代わりに `B. ` で連結するときに増える文字数（`B. ` と `S` と区切りの空白）
+const CONCAT_OVERHEAD: usize = 5;
+
+/// 入れ子の繰り返しで文字列を表す文法
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Node {
+    Literal(String),
+    Concat(Vec<Node>),
+    Repeat(Box<Node>, usize),
+}
+
+impl Node {
+    fn has_repeat(&self) -> bool {
+        match self {
+            Node::Literal(_) => false,
+            Node::Concat(nodes) => nodes.iter().any(Node::has_repeat),
+            Node::Repeat(_, _) => true,
+        }
+    }
+
+    /// 繰り返し用の関数 `repeat` を使って式にする
+    fn expr(&self, repeat: &Expr) -> Expr {
+        match self {
+            Node::Literal(s) => string(s),
+            Node::Concat(nodes) => nodes
+                .iter()
+                .rev()
+                .map(|node| node.expr(repeat))
+                .reduce(|rhs, lhs| concat(lhs, rhs))
+                .expect("Concat is not empty"),
+            Node::Repeat(node, count) => call(repeat.clone(), [node.expr(repeat), int(*count)]),
+        }
+    }
+}
+
+/// 整数を 94 進数で表したときの桁数
+fn count_len(mut count: usize) -> usize {
+    let mut len = 1;
+    while count >= 94 {
+        count /= 94;
+        len += 1;
+    }
+    len
+}
+
+/// 左から順に `s[i..]` から始まる繰り返しを調べる
+///
+/// 周期ごとに一致が続く範囲を覚えておくので、同じ範囲を何度も比べ直さない。
+struct Repeats<'a> {
+    s: &'a [u8],
+    /// 周期 `p` で `s[j] == s[j - p]` が `ends[p].0` から `ends[p].1` の手前まで続く
+    ends: Vec<(usize, usize)>,
+}
+
+impl<'a> Repeats<'a> {
+    fn new(s: &'a [u8]) -> Self {
+        Repeats {
+            s,
+            ends: vec![(0, 0); MAX_PERIOD + 1],
+        }
+    }
+
+    /// `s[i..]` から始まる周期 `period` の繰り返し回数
+    fn repetitions(&mut self, i: usize, period: usize) -> usize {
+        let s = self.s;
+        let start = i + period;
+        let (from, end) = &mut self.ends[period];
+        if !(*from..=*end).contains(&start) {
+            *from = start;
+            *end = (start..s.len())
+                .find(|&j| s[j] != s[j - period])
+                .unwrap_or(s.len());
+        }
+        1 + (*end - start) / period
+    }
+
+    /// `s[i..]` から始まる繰り返しのうち最も文字数を減らせるもの (削減量, 周期, 回数)
+    ///
+    /// 繰り返す部分は文字列のまま置くものとして見積もる。
+    fn best(&mut self, i: usize) -> Option<(usize, usize, usize)> {
+        (1..=MAX_PERIOD.min((self.s.len() - i) / 2))
+            .filter_map(|period| {
+                let count = self.repetitions(i, period);
+                if count < 2 {
+                    return None;
+                }
+                let cost = period + REPEAT_OVERHEAD + CONCAT_OVERHEAD + count_len(count);
+                (count * period > cost).then(|| (count * period - cost, period, count))
+            })
+            .max()
+    }
+}
+
+/// 左から順に、その位置から始まる繰り返しを貪欲に取り出して文法を作る
+///
+/// 次の位置から始まる繰り返しの方が得なら今の位置では取り出さない。
+/// 繰り返す部分も再帰的に圧縮する。
+fn infer(s: &[u8]) -> Node {
+    let mut nodes = vec![];
+    let mut literal = vec![];
+    let mut repeats = Repeats::new(s);
+    let mut i = 0;
+    let mut current = (!s.is_empty()).then(|| repeats.best(0)).flatten();
+    while i < s.len() {
+        let next = (i + 1 < s.len()).then(|| repeats.best(i + 1)).flatten();
+        match current {
+            Some((saving, period, count))
+                if next.is_none_or(|(next_saving, _, _)| saving >= next_saving) =>
+            {
+                if !literal.is_empty() {
+                    nodes.push(Node::Literal(
+                        String::from_utf8_lossy(&literal).into_owned(),
+                    ));
+                    literal.clear();
+                }
+                nodes.push(Node::Repeat(Box::new(infer(&s[i..i + period])), count));
+                i += period * count;
+                current = (i < s.len()).then(|| repeats.best(i)).flatten();
+            }
+            _ => {
+                literal.push(s[i]);
+                i += 1;
+                current = next;
+            }
+        }
+    }
+    if !literal.is_empty() {
+        nodes.push(Node::Literal(
+            String::from_utf8_lossy(&literal).into_owned(),
+        ));
+    }
+    if nodes.len() == 1 {
+        nodes.pop().expect("unreachable")
+    } else {
+        Node::Concat(nodes)
+    }
+}
+
+/// `prefix` に続けて `path` を出力するプログラムを、`path` の繰り返し構造を使って作る
+pub fn encode(prefix: &str, path: &str) -> Vec<Token> {
+    let node = match infer(path.as_bytes()) {
+        node if prefix.is_empty() => node,
+        Node::Literal(s) => Node::Literal(format!("{prefix}{s}")),
+        Node::Concat(mut nodes) => {
+            match &mut nodes[0] {
+                Node::Literal(s) => s.insert_str(0, prefix),
+                _ => nodes.insert(0, Node::Literal(prefix.to_owned())),
+            }
+            Node::Concat(nodes)
+        }
+        node => Node::Concat(vec![Node::Literal(prefix.to_owned()), node]),
+    };
+    if !node.has_repeat() {
+        // 繰り返しがなければ関数は参照されない
+        return node.expr(&repeat()).to_tokens();
+    }
+    // 繰り返し用の関数を束縛して使い回す
+    let_in(repeat(), |repeat| node.expr(&repeat)).to_tokens()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+
+    /// 連結が深くなるので大きなスタックのスレッドで評価する
+    fn eval(tokens: Vec<Token>) -> anyhow::Result<String> {
+        thread::Builder::new()
+            .stack_size(1 << 30)
+            .spawn(move || crate::eval_tokens(&tokens))?
+            .join()
+            .expect("Evaluation thread panicked")
+    }
+
+    #[test]
+    fn infer_nested_repeat() {
+        let path = format!("{}D{}D", "R".repeat(30), "L".repeat(30)).repeat(5);
+        assert_eq!(
+            infer(path.as_bytes()),
+            Node::Repeat(
+                Box::new(Node::Concat(vec![
+                    Node::Repeat(Box::new(Node::Literal("R".into())), 30),
+                    Node::Literal("D".into()),
+                    Node::Repeat(Box::new(Node::Literal("L".into())), 30),
+                    Node::Literal("D".into()),
+                ])),
+                5
+            )
+        );
+    }
+
+    #[test]
+    fn encode_lambdaman_paths() -> anyhow::Result<()> {
+        for (problem_name, author) in [
+            ("lambdaman6", "toga.beam"),
+            ("lambdaman8", "toga.beam"),
+            ("lambdaman16", "toga.beam"),
+        ] {
+            let path =
+                std::fs::read_to_string(format!("data/lambdaman/{problem_name}.{author}.out"))?;
+            let prefix = format!("solve {problem_name} ");
+            let tokens = encode(&prefix, path.trim());
+            assert!(
+                super::super::encode(&tokens)?.len() < path.len(),
+                "{problem_name}"
+            );
+            assert_eq!(eval(tokens)?, format!("{prefix}{}", path.trim()));
+        }
+        Ok(())
+    }
+}
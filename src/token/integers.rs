@@ -1,43 +1,174 @@
 use anyhow::bail;
-use num_bigint::BigInt;
-use num_traits::Euclid;
+use indicatif::ProgressBar;
+use num_bigint::{BigInt, BigUint};
+use num_traits::{Euclid, One, Signed};
+
+/// u64 に収まる 94 進数の桁数 (94^9 < 2^64)
+const CHUNK_DIGITS: usize = 9;
+
+/// `94^CHUNK_DIGITS` のビット数の下限
+const CHUNK_BITS: u64 = 58;
+
+/// これより大きい除数は逆数を掛けて割る (num-bigint の割り算は桁数の 2 乗かかる)
+const RECIPROCAL_BITS: u64 = 4096;
+
+/// `i < levels` について `94^(CHUNK_DIGITS * 2^i)` を並べたもの
+fn powers(levels: usize) -> Vec<BigUint> {
+    let mut powers = vec![BigUint::from(94u64.pow(CHUNK_DIGITS as u32))];
+    while powers.len() < levels {
+        let last = &powers[powers.len() - 1];
+        powers.push(last * last);
+    }
+    powers
+}
+
+/// 上位半分が正しい `floor(2^(2n) / d)` (n は d のビット数) の近似 `x` を
+/// ニュートン法の 1 回の反復で正しい値にする
+fn refine(d: &BigUint, x: BigUint) -> BigUint {
+    let n = d.bits();
+    let one = BigInt::one() << (2 * n);
+    let (d, x) = (BigInt::from(d.clone()), BigInt::from(x));
+    let e = &one - &d * &x;
+    let mut x = &x + ((&x * e) >> (2 * n));
+    // 誤差は数単位なので足し引きして合わせる
+    let mut r = one - &d * &x;
+    while r.is_negative() {
+        x -= 1;
+        r += &d;
+    }
+    while r >= d {
+        x += 1;
+        r -= &d;
+    }
+    x.magnitude().clone()
+}
+
+/// 同じ数で何度も割るための除数
+struct Divisor {
+    divisor: BigUint,
+    reciprocal: Option<BigUint>,
+}
+
+impl Divisor {
+    fn new(divisor: BigUint) -> Self {
+        let reciprocal = (divisor.bits() > RECIPROCAL_BITS)
+            .then(|| (BigUint::one() << (2 * divisor.bits())) / &divisor);
+        Divisor {
+            divisor,
+            reciprocal,
+        }
+    }
+
+    /// 2 乗した除数（逆数は 2 乗を近似として求める）
+    fn square(&self) -> Self {
+        let divisor = &self.divisor * &self.divisor;
+        let Some(reciprocal) = &self.reciprocal else {
+            return Divisor::new(divisor);
+        };
+        let shift = 4 * self.divisor.bits() - 2 * divisor.bits();
+        let reciprocal = refine(&divisor, (reciprocal * reciprocal) >> shift);
+        Divisor {
+            divisor,
+            reciprocal: Some(reciprocal),
+        }
+    }
+
+    /// `value < divisor^2` の商と余り
+    fn div_rem(&self, value: BigUint) -> (BigUint, BigUint) {
+        let Some(reciprocal) = &self.reciprocal else {
+            return value.div_rem_euclid(&self.divisor);
+        };
+        // 商は真の値より高々 2 小さい
+        let mut quotient = (&value * reciprocal) >> (2 * self.divisor.bits());
+        let mut remainder = value - &quotient * &self.divisor;
+        while remainder >= self.divisor {
+            quotient += 1u32;
+            remainder -= &self.divisor;
+        }
+        (quotient, remainder)
+    }
+}
+
+/// `digits` (`0`-`93` の並び) を上位と下位に分けて再帰的に整数にする
+fn from_digits(digits: &[u8], powers: &[BigUint]) -> BigUint {
+    if digits.len() <= CHUNK_DIGITS {
+        return BigUint::from(digits.iter().fold(0u64, |acc, &d| acc * 94 + u64::from(d)));
+    }
+    // 下位を CHUNK_DIGITS * 2^i 桁とする最大の i
+    let level = (0..powers.len())
+        .rev()
+        .find(|&i| CHUNK_DIGITS << i < digits.len())
+        .expect("unreachable");
+    let (high, low) = digits.split_at(digits.len() - (CHUNK_DIGITS << level));
+    from_digits(high, powers) * &powers[level] + from_digits(low, powers)
+}
 
 pub fn decode(stream: impl Iterator<Item = u8>) -> anyhow::Result<BigInt> {
-    let mut ret = BigInt::ZERO;
+    let mut digits = vec![];
     for b in stream {
         match b {
-            b'!'..=b'~' => {
-                ret *= 94;
-                ret += b - b'!';
-            }
+            b'!'..=b'~' => digits.push(b - b'!'),
             _ => bail!("Unexpected char"),
         }
     }
-    Ok(ret)
+    let levels = (0..)
+        .find(|&i| CHUNK_DIGITS << i >= digits.len())
+        .expect("unreachable");
+    Ok(from_digits(&digits, &powers(levels)).into())
 }
 
-pub fn encode(mut value: BigInt) -> anyhow::Result<String> {
+/// `value < 94^(CHUNK_DIGITS * 2^level)` を `CHUNK_DIGITS * 2^level` 桁で上位から `result` に書き込む
+fn to_digits(
+    value: BigUint,
+    level: usize,
+    divisors: &[Divisor],
+    result: &mut Vec<u8>,
+    bar: &ProgressBar,
+) {
+    if level == 0 {
+        let mut value = value.iter_u64_digits().next().unwrap_or(0);
+        let start = result.len();
+        for _ in 0..CHUNK_DIGITS {
+            result.push((value % 94) as u8 + b'!');
+            value /= 94;
+        }
+        result[start..].reverse();
+        bar.inc(CHUNK_DIGITS as u64);
+        return;
+    }
+    let (quotient, remainder) = divisors[level - 1].div_rem(value);
+    to_digits(quotient, level - 1, divisors, result, bar);
+    to_digits(remainder, level - 1, divisors, result, bar);
+}
+
+pub fn encode(value: BigInt) -> anyhow::Result<String> {
+    encode_with_progress(value, &ProgressBar::hidden())
+}
+
+/// 変換した桁数を `bar` に表示しながらエンコードする（`bar` は始めに戻すので使い回せる）
+pub fn encode_with_progress(value: BigInt, bar: &ProgressBar) -> anyhow::Result<String> {
     // value < 0
-    if value < BigInt::ZERO {
+    let Some(value) = value.to_biguint() else {
         bail!("Value must be non-negative");
-    }
+    };
 
-    let mut result = String::new();
-    let bar = indicatif::ProgressBar::new(value.bits());
-    let divisor = BigInt::from(94);
-    while value > BigInt::ZERO {
-        bar.set_position(value.bits());
-        let (quotient, reminder) = value.div_rem_euclid(&divisor);
-        let remainder = u8::try_from(&reminder)?;
-        result.push((remainder + b'!') as char);
-        value = quotient;
-    }
-    if result.is_empty() {
-        result.push('!');
+    // 上位から半分ずつに割っていく
+    let level = (0..)
+        .find(|&i| CHUNK_BITS << i >= value.bits())
+        .expect("unreachable");
+    let mut divisors = vec![Divisor::new(BigUint::from(94u64.pow(CHUNK_DIGITS as u32)))];
+    while divisors.len() < level {
+        divisors.push(divisors[divisors.len() - 1].square());
     }
+    bar.reset();
+    bar.set_length((CHUNK_DIGITS << level) as u64);
+    let mut result = vec![];
+    to_digits(value, level, &divisors, &mut result, bar);
+    bar.finish_and_clear();
 
-    // 逆順にする必要があるため、結果を反転
-    let result: String = result.chars().rev().collect();
+    // 上位の余分な 0 を取り除く
+    let zeros = result.iter().take_while(|&&b| b == b'!').count();
+    let result = &result[zeros.min(result.len() - 1)..];
 
-    Ok(result)
+    Ok(String::from_utf8(result.to_vec())?)
 }
@@ -0,0 +1,219 @@
+use super::Token;
+use crate::{
+    ast::{
+        builder::{call, concat, drop, eq, fix, int, ite, lam2, mul, rem, string, sub, take},
+        Expr,
+    },
+    lambdaman::{Direction, Map, MAX_MOVES},
+};
+use itertools::Itertools;
+
+/// 法の候補（94 進数で 2 桁と 3 桁に収まる素数）
+const MODULI: [u64; 2] = [8831, 830579];
+
+/// 1 つの法について試す乗数の数
+const MULTIPLIERS_PER_MODULUS: usize = 8;
+
+/// 乱数 1 回あたりに同じ方向へ進む歩数の上限
+const MAX_STRIDE: usize = 8;
+
+/// 評価で許される β 簡約の回数
+const MAX_BETA_REDUCTIONS: usize = 10_000_000;
+
+/// ループ 1 回あたりの β 簡約の回数（Y コンビネータの展開と 3 引数の適用）
+const BETA_REDUCTIONS_PER_STEP: usize = 4;
+
+/// 線形合同法 `x <- x * multiplier % modulus` で方向を決めるランダムウォーク
+///
+/// 各ステップで `x % 4` 番目の方向 (`U` `D` `L` `R`) に `stride` 歩進むことを `len` 回繰り返す。
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RandomWalk {
+    pub multiplier: u64,
+    pub modulus: u64,
+    pub seed: u64,
+    pub stride: usize,
+    pub len: usize,
+}
+
+impl RandomWalk {
+    pub fn directions(&self) -> impl Iterator<Item = Direction> + '_ {
+        (0..self.len)
+            .scan(self.seed, |x, _| {
+                let dir = Direction::ALL[(*x % 4) as usize];
+                *x = *x * self.multiplier % self.modulus;
+                Some(dir)
+            })
+            .flat_map(|dir| std::iter::repeat_n(dir, self.stride))
+    }
+
+    pub fn moves(&self) -> String {
+        self.directions().map(Direction::to_char).collect()
+    }
+
+    /// `prefix` の後ろにランダムウォークを出力するプログラム
+    ///
+    /// `prefix . Y (λf. λx. λk. if k == 0 then "" else dir(x) . f (x * a % m) (k - 1)) seed len`
+    pub fn tokens(&self, prefix: &str) -> Vec<Token> {
+        let table = Direction::ALL
+            .iter()
+            .map(|dir| dir.to_char().to_string().repeat(self.stride))
+            .join("");
+        let offset = |x: Expr| {
+            let dir = rem(x, int(4));
+            if self.stride == 1 {
+                dir
+            } else {
+                mul(int(self.stride), dir)
+            }
+        };
+        concat(
+            string(prefix),
+            call(
+                fix(|f| {
+                    lam2(|x, k| {
+                        ite(
+                            eq(k.clone(), int(0)),
+                            string(""),
+                            concat(
+                                take(int(self.stride), drop(offset(x.clone()), string(table))),
+                                call(
+                                    f,
+                                    [
+                                        rem(mul(x, int(self.multiplier)), int(self.modulus)),
+                                        sub(k, int(1)),
+                                    ],
+                                ),
+                            ),
+                        )
+                    })
+                }),
+                [int(self.seed), int(self.len)],
+            ),
+        )
+        .to_tokens()
+    }
+}
+
+fn prime_factors(mut n: u64) -> Vec<u64> {
+    let mut factors = vec![];
+    let mut p = 2;
+    while p * p <= n {
+        if n.is_multiple_of(p) {
+            factors.push(p);
+            while n.is_multiple_of(p) {
+                n /= p;
+            }
+        }
+        p += 1;
+    }
+    if n > 1 {
+        factors.push(n);
+    }
+    factors
+}
+
+fn pow_mod(mut base: u64, mut exp: u64, modulus: u64) -> u64 {
+    let mut ret = 1;
+    while exp > 0 {
+        if exp & 1 == 1 {
+            ret = ret * base % modulus;
+        }
+        base = base * base % modulus;
+        exp /= 2;
+    }
+    ret
+}
+
+/// 周期が最大になる（原始根である）1 桁の乗数
+///
+/// 偶数だと `x % 4` が偏るので奇数に限る。
+fn multipliers(modulus: u64) -> Vec<u64> {
+    let factors = prime_factors(modulus - 1);
+    (3..94)
+        .step_by(2)
+        .filter(|&a| {
+            factors
+                .iter()
+                .all(|&p| pow_mod(a, (modulus - 1) / p, modulus) != 1)
+        })
+        .take(MULTIPLIERS_PER_MODULUS)
+        .collect()
+}
+
+/// `max_len` ステップ以内にピルを食べ尽くせればそのステップ数を返す
+fn steps_to_clear(
+    map: &Map,
+    multiplier: u64,
+    modulus: u64,
+    seed: u64,
+    stride: usize,
+    max_len: usize,
+) -> Option<usize> {
+    let mut walker = map.walker();
+    let mut x = seed;
+    for len in 1..=max_len {
+        let dir = Direction::ALL[(x % 4) as usize];
+        for _ in 0..stride {
+            walker.step(dir);
+        }
+        if walker.remaining() == 0 {
+            return Some(len);
+        }
+        x = x * multiplier % modulus;
+    }
+    None
+}
+
+fn program_len(walk: &RandomWalk) -> usize {
+    super::encode(&walk.tokens("")).map_or(usize::MAX, |s| s.len())
+}
+
+/// マップを解くランダムウォークのうちプログラムが最も短いものを探す
+///
+/// 法・乗数・歩幅ごとに `1..=seeds` のシードを試し、ネイティブに実行して歩数を決める。
+/// 歩数の桁数が増えると今までの最良より長くなる場合はそこで打ち切る。
+pub fn search(map: &Map, seeds: u64) -> Option<RandomWalk> {
+    let mut best: Option<(usize, RandomWalk)> = None;
+    for modulus in MODULI {
+        for multiplier in multipliers(modulus) {
+            for stride in 1..=MAX_STRIDE {
+                for seed in 1..=seeds.min(modulus - 1) {
+                    let mut walk = RandomWalk {
+                        multiplier,
+                        modulus,
+                        seed,
+                        stride,
+                        len: 1,
+                    };
+                    // 周期を超えると同じ動きを繰り返すだけになる
+                    let mut max_len = (MAX_MOVES / stride)
+                        .min(MAX_BETA_REDUCTIONS / BETA_REDUCTIONS_PER_STEP)
+                        .min(modulus as usize - 1);
+                    if let Some((best_len, _)) = best {
+                        // len は 94 進数で 1 桁として数えている
+                        let Some(digits) = (best_len + 1).checked_sub(program_len(&walk) + 1)
+                        else {
+                            continue;
+                        };
+                        max_len = max_len.min(94usize.saturating_pow(digits as u32) - 1);
+                    }
+                    let Some(len) = steps_to_clear(map, multiplier, modulus, seed, stride, max_len)
+                    else {
+                        continue;
+                    };
+                    walk.len = len;
+                    let len = program_len(&walk);
+                    if best.as_ref().is_none_or(|(best_len, _)| len < *best_len) {
+                        best = Some((len, walk));
+                    }
+                }
+            }
+        }
+    }
+    best.map(|(_, walk)| walk)
+}
+
+/// `solve {problem_name} ` に続けてマップを解くランダムウォークを出力するプログラム
+pub fn encode(problem_name: &str, map: &Map, seeds: u64) -> Option<Vec<Token>> {
+    search(map, seeds).map(|walk| walk.tokens(&format!("solve {problem_name} ")))
+}
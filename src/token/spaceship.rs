@@ -0,0 +1,130 @@
+use itertools::Itertools;
+use num_bigint::BigInt;
+
+use super::{Token, UnaryOp};
+use crate::ast::{
+    builder::{add, app, concat, div, eq, fix, int, ite, lam, let_in, mul, rem, string, unary},
+    Expr,
+};
+
+/// 1 つの整数に詰める最大桁数（評価時の再帰の深さと多倍長演算のコストを抑えるため）
+const MAX_PACKED_DIGITS: usize = 1000;
+
+fn is_digit(c: char) -> bool {
+    ('1'..='9').contains(&c)
+}
+
+/// `1`-`9` からなる文字列を 9 進数として整数に詰める
+///
+/// 先頭の `1` (= 0) が消えないように番兵として 1 を最上位に置く。
+fn pack(digits: &str) -> BigInt {
+    digits
+        .bytes()
+        .fold(BigInt::from(1), |acc, b| acc * 9 + (b - b'1'))
+}
+
+/// `pack` した整数を元の文字列に戻す関数
+///
+/// `λn. U$ (Y (λf. λn. if n == 1 then 0 else f (n / 9) * 94 + n % 9 + 53)) n`
+///
+/// 9 進数の各桁 d を 94 進数の桁 d + 53 (= 'd+1' の文字コード) に置き換え、`U$` で文字列にする。
+fn decoder() -> Expr {
+    lam(|n| {
+        unary(
+            UnaryOp::ToString,
+            app(
+                fix(|f| {
+                    lam(|n| {
+                        ite(
+                            eq(n.clone(), int(1)),
+                            int(0),
+                            add(
+                                mul(app(f, div(n.clone(), int(9))), int(94)),
+                                add(rem(n, int(9)), int(53)),
+                            ),
+                        )
+                    })
+                }),
+                n,
+            ),
+        )
+    })
+}
+
+enum Piece {
+    Literal(String),
+    Packed(BigInt),
+}
+
+/// 整数に詰めて `B$ v" I...` としたときのエンコード後の文字数
+fn packed_len(digits: &str) -> usize {
+    // "B$ v\" I{} " の 9 文字 + 整数部分
+    super::integers::encode(pack(digits))
+        .map(|s| s.len())
+        .unwrap_or(usize::MAX)
+        + 9
+}
+
+/// 数字 `1`-`9` の連続部分を整数に詰めてエンコードする
+///
+/// 連続部分ごとに文字列のまま送るか整数にするかを選ぶ。
+/// 途中の連続部分を整数にすると前後の文字列と "B. " 2 つ分と "S " が増えるが、
+/// 先頭と末尾では "B. " 1 つで済むのでその分ボーナスがある。
+pub fn encode(s: &str) -> Vec<Token> {
+    let chars = s.chars().collect_vec();
+    let mut pieces: Vec<Piece> = vec![];
+    for (key, chunk) in &chars.iter().enumerate().chunk_by(|(_, &c)| is_digit(c)) {
+        let chunk = chunk.collect_vec();
+        let run = chunk.iter().map(|(_, &c)| c).collect::<String>();
+        let at_head = chunk[0].0 == 0;
+        let at_tail = chunk[chunk.len() - 1].0 == chars.len() - 1;
+        let overhead = match (at_head, at_tail) {
+            (true, true) => 0,
+            (true, false) | (false, true) => 3,
+            (false, false) => 8,
+        };
+        let mut packs = vec![];
+        if key {
+            let chunks = run.chars().chunks(MAX_PACKED_DIGITS);
+            let runs = chunks
+                .into_iter()
+                .map(|c| c.collect::<String>())
+                .collect_vec();
+            // 分割した分だけ "B. " が増える
+            let cost =
+                runs.iter().map(|r| packed_len(r)).sum::<usize>() + 3 * (runs.len() - 1) + overhead;
+            if cost < run.len() {
+                packs = runs.iter().map(|r| pack(r)).collect_vec();
+            }
+        }
+        if packs.is_empty() {
+            match pieces.last_mut() {
+                Some(Piece::Literal(l)) => l.push_str(&run),
+                _ => pieces.push(Piece::Literal(run)),
+            }
+        } else {
+            pieces.extend(packs.into_iter().map(Piece::Packed));
+        }
+    }
+
+    let packed_count = pieces
+        .iter()
+        .filter(|p| matches!(p, Piece::Packed(_)))
+        .count();
+    let body = |decoder: &Expr| {
+        pieces
+            .iter()
+            .rev()
+            .map(|piece| match piece {
+                Piece::Literal(l) => string(l),
+                Piece::Packed(v) => app(decoder.clone(), int(v.clone())),
+            })
+            .reduce(|rhs, lhs| concat(lhs, rhs))
+            .expect("pieces is not empty")
+    };
+    if packed_count <= 1 {
+        return body(&decoder()).to_tokens();
+    }
+    // 復元用の関数を束縛して使い回す
+    let_in(decoder(), |decoder| body(&decoder)).to_tokens()
+}
@@ -0,0 +1,4 @@
+.include "prelude.asm"
+
+; 3 + 1 + 1
+twice Ln inc vn 3
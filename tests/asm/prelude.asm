@@ -0,0 +1,8 @@
+; テスト用の共通マクロ
+.macro inc x
+B+ x 1
+.endm
+
+.macro twice f x
+B$ f B$ f x
+.endm
@@ -0,0 +1,176 @@
+use std::path::Path;
+
+use icfpc2024::{
+    assemble::{assemble, assemble_file, Assembler},
+    ast::{Expr, Value},
+};
+use num_bigint::BigInt;
+use rstest::rstest;
+
+fn eval(tokens: &[String]) -> anyhow::Result<Value> {
+    let expr: Expr = tokens.join(" ").parse()?;
+    expr.eval()
+}
+
+#[test]
+fn legacy_input() -> anyhow::Result<()> {
+    // 変数名を数値で書いた従来の入力はそのまま出力される
+    let tokens = assemble(r#"B$ B$ Y L" L# ? B= v# 0 1 B* v# B$ v" B- v# 1 5"#)?;
+    assert_eq!(
+        tokens.join(" "),
+        r#"B$ B$ L" B$ L# B$ v" B$ v# v# L# B$ v" B$ v# v# L" L# ? B= v# I! I" B* v# B$ v" B- v# I" I&"#
+    );
+    assert_eq!(eval(&tokens)?, BigInt::from(120).into());
+    Ok(())
+}
+
+#[test]
+fn symbolic_variables() -> anyhow::Result<()> {
+    let tokens = assemble("B$ B$ Lx Ly B- vx vy 50 8")?;
+    assert_eq!(tokens.join(" "), r#"B$ B$ L" L# B- v" v# IS I)"#);
+    assert_eq!(eval(&tokens)?, BigInt::from(42).into());
+
+    // 兄弟のスコープでは同じ名前を使い回す
+    let tokens = assemble("B. B$ Lfoo vfoo \"a\" B$ Lbar vbar \"b\"")?;
+    assert_eq!(tokens.join(" "), r#"B. B$ L" v" S! B$ L" v" S""#);
+    Ok(())
+}
+
+#[test]
+fn free_variables_are_kept() -> anyhow::Result<()> {
+    // 束縛されていない v" と重ならないように番号を付ける
+    let tokens = assemble(r#"Lx B$ vx v""#)?;
+    assert_eq!(tokens.join(" "), r#"L# B$ v# v""#);
+    Ok(())
+}
+
+#[test]
+fn comments_and_strings() -> anyhow::Result<()> {
+    let tokens = assemble(
+        r#"
+        ; 空白やエスケープを含む文字列
+        B. "Hello " e"\"World\"\n" ; 行末のコメント
+        "#,
+    )?;
+    assert_eq!(eval(&tokens)?, "Hello \"World\"\n".to_owned().into());
+    Ok(())
+}
+
+#[rstest]
+#[case(r#"B. "Hello\b" "x""#, r"Hello\bx")]
+#[case(r#""a\\b""#, r"a\\b")]
+#[case(r#""say"hi""#, r#"say"hi"#)]
+#[case(r#"e"a\\b""#, r"a\b")]
+fn raw_strings(#[case] asm: &str, #[case] expected: &str) -> anyhow::Result<()> {
+    // `"..."` の `\` は以前と同じくそのまま
+    assert_eq!(eval(&assemble(asm)?)?, expected.to_owned().into());
+    Ok(())
+}
+
+#[test]
+fn macros() -> anyhow::Result<()> {
+    let tokens = assemble(
+        r#"
+        .macro square x
+        B* x x
+        .endm
+        .macro const x
+        Ly x
+        .endm
+        ; 引数は式で、マクロの中の Ly は外側の vy を捕まえない
+        B$ Ly B+ square B+ 1 2 B$ const vy 100 7
+        "#,
+    )?;
+    assert_eq!(eval(&tokens)?, BigInt::from(16).into());
+    Ok(())
+}
+
+#[test]
+fn include() -> anyhow::Result<()> {
+    let tokens = assemble_file(Path::new("tests/asm/main.asm"))?;
+    assert_eq!(eval(&tokens)?, BigInt::from(5).into());
+    Ok(())
+}
+
+#[test]
+fn errors() {
+    let err = assemble(".macro id x\nx").unwrap_err();
+    assert!(err.to_string().contains("not closed"), "{err}");
+    let err = assemble("B+ 1 2 ; ok\nW").unwrap_err();
+    assert!(
+        err.to_string().contains("Unknown mnemonic at 2:1: W"),
+        "{err}"
+    );
+    let err = assemble(r#"B. "abc"#).unwrap_err();
+    assert!(err.to_string().contains("Unterminated string"), "{err}");
+}
+
+#[test]
+fn missing_operands() {
+    let err = assemble("B$ Lx\n  B+ vx").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "B+ at 2:3 is missing operands: expected 2, got 1"
+    );
+
+    let err = assemble(".macro inc x\nB+ x\n.endm\nB$ Lx inc vx").unwrap_err();
+    assert_eq!(
+        format!("{err:#}"),
+        "In macro inc expanded at 4:7: B+ at 2:1 is missing operands: expected 2, got 1"
+    );
+
+    let err = assemble("? T 1").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "? at 1:1 is missing operands: expected 3, got 2"
+    );
+}
+
+#[test]
+fn trailing_tokens() {
+    let err = assemble("B+ 1 2\n3").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Trailing tokens at 2:1: the program must be a single expression"
+    );
+    let err = assemble(".macro pair\n1 2\n.endm\npair").unwrap_err();
+    assert_eq!(
+        format!("{err:#}"),
+        "Macro pair must be a single expression: trailing tokens at 2:3"
+    );
+    assert!(assemble("; empty").is_err());
+}
+
+#[test]
+fn warnings() -> anyhow::Result<()> {
+    let mut assembler = Assembler::new();
+    let tokens = assembler.assemble("B$ Lx Lx B+ vx v#\n1", Path::new("."))?;
+    assert_eq!(tokens.join(" "), r#"B$ L" L$ B+ v$ v# I""#);
+    assert_eq!(
+        assembler.warnings(),
+        ["Unbound variable v# at 1:16", "Lx at 1:7 shadows Lx at 1:4"]
+    );
+
+    // マクロの中の変数は展開ごとに別なので隠蔽にならない
+    assembler.assemble("B$ Y Lf Lx B$ Y Lg vx", Path::new("."))?;
+    assert!(assembler.warnings().is_empty());
+
+    let err = assembler.assemble("vλ", Path::new(".")).unwrap_err();
+    assert!(
+        err.to_string().contains("Unbound variable vλ at 1:1"),
+        "{err}"
+    );
+    Ok(())
+}
+
+#[test]
+fn fixed_numbers() -> anyhow::Result<()> {
+    // @ の後の番号はそのまま使い、それ以外は固定された番号を避ける
+    let tokens = assemble("Lx@0 Ly Lz@2 B+ vx vz")?;
+    assert_eq!(tokens.join(" "), r#"L! L" L# B+ v! v#"#);
+
+    // 外側の変数が内側の固定された番号に取られるならエラー
+    let err = assemble("Lx Ly@1 vx").unwrap_err().to_string();
+    assert!(err.contains("vx at 1:9 is captured by Ly at 1:4"), "{err}");
+    Ok(())
+}
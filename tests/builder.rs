@@ -0,0 +1,40 @@
+use icfpc2024::{
+    ast::{builder::*, Expr, Value},
+    token,
+};
+use num_bigint::BigInt;
+use rstest::rstest;
+
+#[rstest]
+#[case::identity(lam(|x| x), r#"L" v""#)]
+#[case::nested(lam2(|x, _| lam(|_| x)), r#"L" L# L$ v""#)]
+#[case::siblings(app(lam(|x| x), lam(|y| y)), r#"B$ L" v" L" v""#)]
+#[case::let_in(let_in(int(1), |x| add(x.clone(), x)), r#"B$ L" B+ v" v" I""#)]
+#[case::fix(
+    fix(|f| lam(|n| app(f, n))),
+    r#"B$ L" B$ L# B$ v" B$ v# v# L# B$ v" B$ v# v# L" L# B$ v" v#"#
+)]
+fn variables(#[case] expr: Expr, #[case] expected: &str) -> anyhow::Result<()> {
+    assert_eq!(token::encode(&expr.to_tokens())?, expected);
+    Ok(())
+}
+
+#[rstest]
+#[case::arithmetic(sub(mul(int(6), int(7)), rem(div(int(10), int(3)), int(2))), BigInt::from(41).into())]
+#[case::strings(concat(take(int(2), string("abc")), drop(int(2), string("xyz"))), "abz".to_owned().into())]
+#[case::call(call(lam2(sub), [int(5), int(3)]), BigInt::from(2).into())]
+#[case::closure(
+    let_in(int(10), |k| call(lam(|x| lam(|y| add(add(x, y), k))), [int(1), int(2)])),
+    BigInt::from(13).into()
+)]
+#[case::fix(
+    app(
+        fix(|f| lam(|n| ite(eq(n.clone(), int(0)), int(1), mul(n.clone(), app(f, sub(n, int(1))))))),
+        int(10)
+    ),
+    BigInt::from(3628800).into()
+)]
+fn eval(#[case] expr: Expr, #[case] expected: Value) -> anyhow::Result<()> {
+    assert_eq!(expr.eval()?, expected);
+    Ok(())
+}
@@ -0,0 +1,107 @@
+use std::path::Path;
+
+use icfpc2024::catalog::{self, Catalog, Name, Query};
+use rstest::rstest;
+
+#[rstest]
+#[case("lambdaman1.toga.beam.out", Some(("lambdaman1", "toga", Some("beam"))))]
+#[case("3d1.kaku.5259.out", Some(("3d1", "kaku", Some("5259"))))]
+#[case("spaceship1.pes.out", Some(("spaceship1", "pes", None)))]
+#[case("lambdaman6.ryosuke.manual.raw", Some(("lambdaman6", "ryosuke", Some("manual"))))]
+#[case("lambdaman6.raw", None)]
+#[case("spaceship1.in", None)]
+#[case("3d12.ryosuke.manual.py", None)]
+#[case("3d1.kaku.raw", None)]
+fn parse_name(#[case] file_name: &str, #[case] expected: Option<(&str, &str, Option<&str>)>) {
+    let expected = expected.map(|(task, author, method)| Name {
+        task: task.to_owned(),
+        author: author.to_owned(),
+        method: method.map(str::to_owned),
+    });
+    assert_eq!(catalog::parse_name(file_name), expected);
+}
+
+#[rstest]
+#[case("spaceship1", "out")]
+// lambdaman6 は評価すると solve のリクエストになるプログラムが一番短い
+#[case("lambdaman6", "raw")]
+fn best(#[case] task: &str, #[case] ext: &str) {
+    let data = Path::new("data");
+    let query = Query {
+        task: Some(task.to_owned()),
+        ..Default::default()
+    };
+    let catalog = Catalog::scan(data, &query).unwrap();
+    assert!(catalog.entries.iter().all(|entry| entry.task() == task));
+    // `<task>.raw` は課題そのものなので解に含めない
+    assert!(!catalog
+        .entries
+        .iter()
+        .any(|entry| entry.path.ends_with(format!("{task}.raw"))));
+    let best = catalog.best(task).unwrap();
+    assert!(best.verified);
+    assert_eq!(best.path.extension().unwrap(), ext);
+    assert_eq!(
+        best.score,
+        catalog.entries.iter().filter_map(|entry| entry.score).min()
+    );
+    if ext == "raw" {
+        assert_eq!(best.score, best.size());
+    }
+}
+
+#[test]
+fn scan() {
+    let data = Path::new("data");
+    let query = Query {
+        task: Some("3d1".to_owned()),
+        author: Some("kaku".to_owned()),
+        ..Default::default()
+    };
+    let catalog = Catalog::scan(data, &query).unwrap();
+    assert!(!catalog.entries.is_empty());
+    assert!(catalog
+        .entries
+        .iter()
+        .all(|entry| entry.author == "kaku" && entry.size().is_some()));
+    // 止まらない解は確かめられず、理由が残る
+    let hardcoded = catalog
+        .entries
+        .iter()
+        .find(|entry| entry.method.as_deref() == Some("hardcoded"))
+        .unwrap();
+    assert!(!hardcoded.verified);
+    assert!(hardcoded.error.as_ref().unwrap().contains("Stuck"));
+    let best = catalog.best("3d1").unwrap();
+    assert!(best.verified);
+    assert!(catalog
+        .entries
+        .iter()
+        .filter_map(|entry| entry.score)
+        .all(|score| best.score.unwrap() <= score));
+
+    let verified = Query {
+        family: Some("spaceship".to_owned()),
+        verified: true,
+        ..Default::default()
+    };
+    let catalog = Catalog::scan(data, &verified).unwrap();
+    assert!(catalog.entries.iter().all(|entry| entry.score.is_some()));
+}
+
+#[test]
+fn to_json() {
+    let query = Query {
+        family: Some("3d".to_owned()),
+        ..Default::default()
+    };
+    let catalog = Catalog::scan(Path::new("data"), &query).unwrap();
+    let json = serde_json::to_value(&catalog).unwrap();
+    let entries = json.as_array().unwrap();
+    assert_eq!(entries.len(), catalog.entries.len());
+    let first = &entries[0];
+    assert_eq!(first["task"], catalog.entries[0].task());
+    assert_eq!(first["verified"], catalog.entries[0].verified);
+    assert_eq!(first["size"], catalog.entries[0].size().unwrap());
+    assert!(first.get("request").is_none());
+}
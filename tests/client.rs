@@ -0,0 +1,76 @@
+use std::{
+    fs,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use icfpc2024::{
+    client::{log::LogEntry, Client, Config, RateLimiter},
+    mock_server::MockServer,
+};
+
+#[tokio::test]
+async fn rate_limiter_waits_for_refill() {
+    let limiter = RateLimiter::new(2, Duration::from_millis(100));
+    let start = Instant::now();
+    limiter.acquire().await;
+    limiter.acquire().await;
+    assert!(start.elapsed() < Duration::from_millis(50));
+    limiter.acquire().await;
+    assert!(start.elapsed() >= Duration::from_millis(90));
+}
+
+#[tokio::test]
+async fn gives_up_after_retries() {
+    // 誰も待ち受けていないポートには接続できない
+    let config = Config {
+        retries: 2,
+        backoff: Duration::from_millis(10),
+        timeout: Duration::from_secs(1),
+        ..Config::new("http://127.0.0.1:9/communicate", "token")
+    };
+    let limiter = Arc::new(RateLimiter::new(10, Duration::from_millis(1)));
+    let client = Client::new(config).unwrap().with_limiter(limiter);
+    let start = Instant::now();
+    let err = client.send_raw("S'%4}).$%8").await.unwrap_err();
+    assert!(err.to_string().contains("Gave up after 2 retries"), "{err}");
+    // 10ms と 20ms 待つ
+    assert!(start.elapsed() >= Duration::from_millis(30));
+}
+
+#[tokio::test]
+async fn records_and_replays() {
+    let path = std::env::temp_dir().join(format!("icfpc2024-log-{}.jsonl", std::process::id()));
+    let _ = fs::remove_file(&path);
+    let limiter = Arc::new(RateLimiter::new(10, Duration::from_millis(1)));
+
+    let handle = MockServer::new("data").bind("127.0.0.1:0").await.unwrap();
+    let config = Config {
+        log: Some(path.clone()),
+        ..Config::new(&handle.url(), "token")
+    };
+    let client = Client::new(config.clone())
+        .unwrap()
+        .with_limiter(limiter.clone());
+    let reply = client.request("echo hello").await.unwrap();
+    drop(handle);
+
+    let log = fs::read_to_string(&path).unwrap();
+    let entry = serde_json::from_str::<LogEntry>(log.trim_end()).unwrap();
+    assert_eq!(entry.decoded_request.as_deref(), Some("echo hello"));
+    assert_eq!(entry.decoded_response, Some(reply.clone()));
+
+    // サーバーを止めてもログから答える
+    let replay = Client::new(Config {
+        replay: true,
+        ..config
+    })
+    .unwrap()
+    .with_limiter(limiter);
+    assert_eq!(replay.request("echo hello").await.unwrap(), reply);
+    let err = replay.request("echo bye").await.unwrap_err();
+    assert!(err.to_string().starts_with("No response for"), "{err}");
+    // リプレイは追記しない
+    assert_eq!(fs::read_to_string(&path).unwrap(), log);
+    fs::remove_file(&path).unwrap();
+}
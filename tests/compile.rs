@@ -0,0 +1,77 @@
+use icfpc2024::{
+    ast::{Expr, Value},
+    compile::compile,
+    token,
+};
+use num_bigint::BigInt;
+use rstest::rstest;
+
+fn int(i: i64) -> Value {
+    BigInt::from(i).into()
+}
+
+fn string(s: &str) -> Value {
+    s.to_owned().into()
+}
+
+#[rstest]
+#[case::arithmetic("1 + 2 * 3 - 4 / 2 % 3", int(5))]
+#[case::negative("-(2 + 3) * 4", int(-20))]
+#[case::comparison("1 < 2 && 2 >= 2 && 3 != 4 && !(1 > 2) && 2 <= 1 || 1 == 1", true.into())]
+#[case::concat(r#""Hello" ++ ", " ++ "World!""#, string("Hello, World!"))]
+#[case::escape(r#""a\"b\\c\n""#, string("a\"b\\c\n"))]
+#[case::let_in("let x = 20 in let y = x + 1 in x + y + 1", int(42))]
+#[case::lambda("(fn x y -> x - y) 10 3", int(7))]
+#[case::backslash(r"(\f -> f (f 1)) (\x -> x * 3)", int(9))]
+#[case::let_function("let add x y = x + y in add 40 2", int(42))]
+#[case::if_else("if 1 > 2 then \"yes\" else \"no\"", string("no"))]
+#[case::shadowing("let x = 1 in let x = x + 1 in let f y = x * y in f 21", int(42))]
+#[case::builtins(r#"int_to_str (str_to_int (take 3 (drop 1 "xyzw")))"#, string("yzw"))]
+#[case::partial_builtin(r#"let first = take 1 in first "abc" ++ (drop 2) "abc""#, string("ac"))]
+#[case::factorial(
+    "let rec fact n = if n == 0 then 1 else n * fact (n - 1) in fact 10",
+    int(3628800)
+)]
+#[case::repeat(
+    r#"
+    # 文字列 s を n 回繰り返す
+    let rec repeat s n =
+        if n == 0 then "" else s ++ repeat s (n - 1)
+    in
+    "solve lambdaman1 " ++ repeat "LR" 3
+    "#,
+    string("solve lambdaman1 LRLRLR")
+)]
+fn eval(#[case] src: &str, #[case] expected: Value) -> anyhow::Result<()> {
+    let tokens = compile(src)?;
+    assert_eq!(Expr::from_tokens(&tokens)?.eval()?, expected);
+    Ok(())
+}
+
+#[rstest]
+#[case::let_in("let x = 1 in x + x", r#"B$ L" B+ v" v" I""#)]
+#[case::nested("fn a b c -> a", r#"L" L# L$ v""#)]
+#[case::reuse("(fn a -> a) (fn b -> b)", r#"B$ L" v" L" v""#)]
+#[case::shadowed("fn x -> fn x -> x", r#"L" L" v""#)]
+#[case::let_rec(
+    "let rec f n = f n in f",
+    r#"B$ L" v" B$ L" B$ L# B$ v" B$ v# v# L# B$ v" B$ v# v# L" L# B$ v" v#"#
+)]
+fn shortest_names(#[case] src: &str, #[case] expected: &str) -> anyhow::Result<()> {
+    assert_eq!(token::encode(&compile(src)?)?, expected);
+    Ok(())
+}
+
+#[rstest]
+#[case::unbound("let x = 1 in\n  x + y", "Unbound variable y at 2:7")]
+#[case::missing_in("let x = 1 then x", "Expected 'in' at 1:11")]
+#[case::missing_then("if 1 < 2 else 3", "Expected 'then' at 1:10")]
+#[case::no_params("fn -> 1", "Expected parameters at 1:4")]
+#[case::unclosed("(1 + 2", "Expected ')' at 2:1")]
+#[case::trailing("1 + 2 )", "Unexpected input at 1:7")]
+#[case::unterminated(r#""abc"#, "Unterminated string at 1:1")]
+#[case::unknown_char("1 @ 2", "Unexpected character at 1:3: @")]
+fn errors(#[case] src: &str, #[case] message: &str) {
+    let err = compile(src).unwrap_err().to_string();
+    assert!(err.contains(message), "{err}");
+}
@@ -0,0 +1,54 @@
+use std::{fs, thread};
+
+use icfpc2024::{assemble::assemble, disassemble::disassemble, token};
+use rstest::rstest;
+
+/// 逆アセンブルしてアセンブルし直す
+fn round_trip(program: &str) -> anyhow::Result<String> {
+    let tokens = token::decode_token_stream(program.trim())?;
+    let asm = disassemble(&tokens)?;
+    assert_eq!(assemble(&asm)?.join(" "), token::encode(&tokens)?);
+    Ok(asm)
+}
+
+#[rstest]
+#[case::string(r#"S'%4}).$%8"#, r#""get index""#)]
+#[case::escape(r#"B. S3!9} S`()`v~"#, r#"B. "say " e"\"hi\"\\\n""#)]
+#[case::literals("? B> I# I$ T F", "? B> 2 3 T F")]
+#[case::lambda(r#"B$ L" B+ v" v" I#"#, "B$ La B+ va va 2")]
+#[case::renumbered(r#"L# L" B+ v# v""#, "La@2 Lb B+ va vb")]
+#[case::shadowed(r#"L" L" v""#, "La Lb@1 vb")]
+#[case::free(r#"B$ L" B+ v" va I#"#, "B$ Lb B+ vb va 2")]
+#[case::zero("L! v!", "La@0 va")]
+#[case::nested(
+    r#"B$ B$ L" B$ L# B$ v" B$ v# v# L# B$ v" B$ v# v# L" L# ? B= v# I! S B. S/ B$ v" B- v# I" I$"#,
+    r#"B$
+  B$
+    La B$ Lb B$ va B$ vb vb Lb B$ va B$ vb vb
+    La Lb ? B= vb 0 "" B. "o" B$ va B- vb 1
+  3"#
+)]
+fn readable(#[case] program: &str, #[case] expected: &str) -> anyhow::Result<()> {
+    assert_eq!(round_trip(program)?.trim_end(), expected);
+    Ok(())
+}
+
+#[test]
+fn data_round_trip() -> anyhow::Result<()> {
+    // 深い式もあるので大きなスタックのスレッドで実行する
+    thread::Builder::new()
+        .stack_size(1 << 30)
+        .spawn(|| -> anyhow::Result<()> {
+            for dir in fs::read_dir("data")? {
+                for entry in fs::read_dir(dir?.path())? {
+                    let path = entry?.path();
+                    if path.extension().is_some_and(|ext| ext == "raw") {
+                        round_trip(&fs::read_to_string(&path)?)?;
+                    }
+                }
+            }
+            Ok(())
+        })?
+        .join()
+        .expect("Round trip thread panicked")
+}
@@ -0,0 +1,91 @@
+use std::{fs, thread};
+
+use icfpc2024::{lambdaman::Map, token};
+use rstest::rstest;
+
+/// 評価器は再帰が深いので大きなスタックのスレッドで実行する
+fn eval_with_large_stack(tokens: Vec<token::Token>) -> anyhow::Result<String> {
+    thread::Builder::new()
+        .stack_size(1 << 30)
+        .spawn(move || icfpc2024::eval_tokens(&tokens))?
+        .join()
+        .expect("Evaluation thread panicked")
+}
+
+#[test]
+fn spaceship_outputs() -> anyhow::Result<()> {
+    for entry in fs::read_dir("data/spaceship")? {
+        let path = entry?.path();
+        if path.extension().is_none_or(|ext| ext != "out") {
+            continue;
+        }
+        let file_name = path.file_name().unwrap().to_string_lossy().to_string();
+        let problem_name = file_name.split('.').next().unwrap();
+        let cmd = format!("solve {problem_name} {}", fs::read_to_string(&path)?.trim());
+
+        let tokens = token::encode_string(&cmd)?;
+        let plain_len = token::encode(&[token::Token::String(cmd.clone())])?.len();
+        assert!(token::encode(&tokens)?.len() <= plain_len, "{file_name}");
+        assert_eq!(eval_with_large_stack(tokens)?, cmd, "{file_name}");
+    }
+    Ok(())
+}
+
+#[test]
+fn lambdaman_path_followed_by_whitespace() -> anyhow::Result<()> {
+    // 経路の後ろに改行があっても経路を文字列の途中で切らない
+    let cmd = format!("solve lambdaman4 {}\n", "RRRRDDDDLLLLUUUU".repeat(50));
+    let tokens = token::encode_string(&cmd)?;
+    assert_eq!(eval_with_large_stack(tokens)?, cmd);
+    Ok(())
+}
+
+#[test]
+fn lambdaman_random_walk() -> anyhow::Result<()> {
+    for problem_name in ["lambdaman1", "lambdaman2", "lambdaman3"] {
+        let map: Map = fs::read_to_string(format!("data/lambdaman/{problem_name}.in"))?.parse()?;
+        let tokens = token::random_walk::encode(problem_name, &map, 4).expect(problem_name);
+        let result = eval_with_large_stack(tokens)?;
+        let moves = result
+            .strip_prefix(&format!("solve {problem_name} "))
+            .expect(problem_name);
+        assert_eq!(map.simulate(moves)?, 0, "{problem_name}");
+    }
+    Ok(())
+}
+
+/// 決まった種から作る `len` 手の経路（テストの入力を実行ごとに同じにする）
+fn moves(len: usize) -> String {
+    let mut x = 7u64;
+    (0..len)
+        .map(|_| {
+            x = x
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            ['D', 'L', 'R', 'U'][(x >> 60) as usize % 4]
+        })
+        .collect()
+}
+
+/// `moves` の向きごとに 1 から 16 手ずつ進む `count` 回の経路
+fn runs(count: usize) -> String {
+    moves(count)
+        .chars()
+        .zip(moves(count).chars().rev())
+        .map(|(c, n)| c.to_string().repeat(1 + "DLRU".find(n).unwrap() * 5))
+        .collect()
+}
+
+/// 候補の選び方が変わっていないか（どの候補が選ばれるかも入力ごとに決まっている）
+#[rstest]
+#[case::plain("get index".to_owned(), "S'%4}).$%8")]
+#[case::grammar(format!("solve lambdaman3 {}", "RDLLLURRD".repeat(30)), "B$ L\" B. S3/,6%},!-\"$!-!.X} B$ B$ v\" SL>FFFOLL> I? B$ L\" B$ L# B$ v\" B$ v# v# L# B$ v\" B$ v# v# L\" L# L$ ? B= v$ I! S B. v# B$ B$ v\" v# B- v$ I\"")]
+#[case::base(format!("solve lambdaman4 {}", moves(300)), "B. S3/,6%},!-\"$!-!.Y} B$ B$ L\" B$ L# B$ v\" B$ v# v# L# B$ v\" B$ v# v# L\" L# ? B= v# I! S B. B$ v\" B/ v# I% ? B= B% v# I% I! S> ? B= B% v# I% I\" SF ? B= B% v# I% I# SL SO I,5Bx=CT~6%*piL-A]Z0$YGL#?.15FJ8=tWqC2}b?/Hysatt]3KU&nX{vQIv3xRSkz&n9}{:4):U!>iNrRuc4CRKcDMKA")]
+#[case::run_length(format!("solve lambdaman5 {}", runs(150)), "B. S3/,6%},!-\"$!-!.Z} B$ B$ L\" B$ L# B$ v\" B$ v# v# L# B$ v\" B$ v# v# L\" L# ? B= v# I! S B. B$ v\" B/ v# Ie B$ L$ B$ B$ L% B$ L& B$ v% B$ v& v& L& B$ v% B$ v& v& L% L& ? B= v& I! v$ B. v$ B$ v% B- v& I\" B% B/ v# I% I2 ? B= B% v# I% I! S> ? B= B% v# I% I\" SF ? B= B% v# I% I# SL SO I=&m6`GmJzSm=Wb7kMnN@>B31C7?:t]GO!dWkc4wU[eb~oCc%h|e*DN_gDv6a'bmQ4#`,@)q_NpBky-MeyTwj/$dn#Wl#E9=WT\\`;#Nk+9guBM-}J:E->\\4o_w")]
+#[case::spaceship(format!("solve spaceship2 {}", moves(300).replace('D', "2").replace('L', "4").replace('R', "6").replace('U', "8")), "B. S3/,6%}30!#%3()0W} B$ L\" U$ B$ B$ L# B$ L$ B$ v# B$ v$ v$ L$ B$ v# B$ v$ v$ L# L$ ? B= v$ I\" I! B+ B* B$ v# B/ v$ I* I\"! B+ B% v$ I* IV v\" I#hbdX@HyQeov+0r'X\\\"REx`@i/3O@sI}Mo8qcXMMe;weM7Y]08\\W]'Y=Dh0@v6O0aeJ(/zU\"m>\"IPE(5CScMrQeQ,]JOLp9Li{;YQ|+<fz+z!_j^1T%`>ysTpi+}cxFc]:1dmNMj{\"[WaZ=a{j")]
+fn encode_string_golden(#[case] s: String, #[case] expected: &str) -> anyhow::Result<()> {
+    let tokens = token::encode_string(&s)?;
+    assert_eq!(token::encode(&tokens)?, expected);
+    assert_eq!(eval_with_large_stack(tokens)?, s);
+    Ok(())
+}
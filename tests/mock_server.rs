@@ -0,0 +1,119 @@
+use std::{fs, sync::Arc, time::Duration};
+
+use icfpc2024::{
+    client::{Client, Config, RateLimiter},
+    mock_server::{MockHandle, MockServer},
+    tasks,
+};
+use rstest::rstest;
+
+async fn client(server: MockServer) -> (MockHandle, Client) {
+    let handle = server.bind("127.0.0.1:0").await.unwrap();
+    let config = Config {
+        retries: 0,
+        backoff: Duration::from_millis(10),
+        ..Config::new(&handle.url(), "token")
+    };
+    let limiter = Arc::new(RateLimiter::new(100, Duration::from_millis(1)));
+    let client = Client::new(config).unwrap().with_limiter(limiter);
+    (handle, client)
+}
+
+#[tokio::test]
+async fn serves_tasks_from_data() {
+    let (_handle, client) = client(MockServer::new("data")).await;
+    // 保存してある応答をそのまま返すので、評価すると .in になる
+    let task = client.request("get lambdaman1").await.unwrap();
+    assert_eq!(
+        task,
+        fs::read_to_string("data/lambdaman/lambdaman1.in").unwrap()
+    );
+
+    let index = client.request("get index").await.unwrap();
+    assert!(index.contains("[spaceship]"), "{index}");
+}
+
+#[rstest]
+#[case(
+    "solve lambdaman1 UDLLLDURRRRRURR",
+    "Correct, you solved lambdaman1 with a score of"
+)]
+#[case(
+    "solve lambdaman1 UDL",
+    "Your solution for lambdaman1 is wrong: 8 left unvisited."
+)]
+#[case(
+    "solve spaceship1 31619",
+    "Correct, you solved spaceship1 with a score of 5!"
+)]
+#[case(
+    "solve spaceship1 0",
+    "Your solution for spaceship1 is invalid: Unexpected move: 0"
+)]
+#[case("echo hi", "hi\n\nYou scored some points for using the echo service!")]
+#[case("hello", "Unknown command: hello")]
+#[tokio::test]
+async fn responds(#[case] message: &str, #[case] expected: &str) {
+    let (_handle, client) = client(MockServer::new("data")).await;
+    let reply = client.request(message).await.unwrap();
+    assert!(reply.starts_with(expected), "{reply}");
+}
+
+#[tokio::test]
+async fn records_best_score() {
+    let (_handle, client) = client(MockServer::new("data")).await;
+    client
+        .request("solve lambdaman1 UDLLLDURRRRRURR")
+        .await
+        .unwrap();
+    let page = client.request("get lambdaman").await.unwrap();
+    assert!(page.contains("* [lambdaman1] Your score: "), "{page}");
+    assert!(page.contains("* [lambdaman2]\n"), "{page}");
+}
+
+#[tokio::test]
+async fn enforces_rate_limit() {
+    let (_handle, client) = client(MockServer::new("data").with_rate_limit(1)).await;
+    client.request("echo 1").await.unwrap();
+    let err = client.request("echo 2").await.unwrap_err();
+    assert!(format!("{err:#}").contains("Empty response"), "{err:#}");
+}
+
+#[tokio::test]
+async fn reads_multiline_solutions() {
+    let (_handle, client) = client(MockServer::new("data")).await;
+    let reply = client.request("solve 3d1\n. S\n").await.unwrap();
+    assert_eq!(reply, "Mock server cannot check 3d solutions.");
+}
+
+#[tokio::test]
+async fn downloads_efficiency_without_evaluating() {
+    // 以前はログに残すときに応答を評価して、efficiency2 でスタックが溢れていた
+    let handle = MockServer::new("data").bind("127.0.0.1:0").await.unwrap();
+    let dir = std::env::temp_dir().join(format!("icfpc2024-download-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let config = Config {
+        log: Some(dir.join("requests.jsonl")),
+        ..Config::new(&handle.url(), "token")
+    };
+    let client = Client::new(config).unwrap();
+
+    let downloaded = tasks::download(&client, "efficiency", "efficiency2", &dir)
+        .await
+        .unwrap();
+    assert_eq!(
+        fs::read_to_string(dir.join("efficiency2.raw")).unwrap(),
+        fs::read_to_string("data/efficiency/efficiency2.raw").unwrap()
+    );
+    assert_eq!(
+        downloaded.decoded_file.unwrap(),
+        dir.join("efficiency2.ast")
+    );
+    assert_eq!(
+        fs::read_to_string(dir.join("efficiency2.ast")).unwrap(),
+        fs::read_to_string("data/efficiency/efficiency2.ast").unwrap()
+    );
+    let log = fs::read_to_string(dir.join("requests.jsonl")).unwrap();
+    assert!(log.contains("\"decoded_response\":null"), "{log}");
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,47 @@
+use std::{
+    fs,
+    path::Path,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use icfpc2024::{catalog::Entry, score};
+use rstest::rstest;
+
+/// `answer` を `<task>.test.out` に書いて確かめる
+fn load(task: &str, answer: &str) -> Entry {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+    let dir = std::env::temp_dir().join(format!(
+        "icfpc2024-score-{}-{}",
+        std::process::id(),
+        COUNT.fetch_add(1, Ordering::Relaxed)
+    ));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(format!("{task}.test.out"));
+    fs::write(&path, answer).unwrap();
+    let entry = Entry::load(Path::new("data"), &path).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    entry
+}
+
+#[rstest]
+#[case("lambdaman1", "UDLLLDURRRRRURR", Some(score::solve_request("lambdaman1", "UDLLLDURRRRRURR").unwrap().len() as u64))]
+#[case("spaceship1", "31619\n", Some(5))]
+#[case("3d1", "solve 3d1\n. 24 .\nA * S\n", Some(6))]
+#[case("efficiency1", "anything", None)]
+fn local_score(#[case] task: &str, #[case] answer: &str, #[case] expected: Option<u64>) {
+    let entry = load(task, answer);
+    assert_eq!(entry.score, expected);
+    assert_eq!(entry.error, None);
+}
+
+#[rstest]
+#[case("lambdaman1", "UDL", "8 pills left")]
+#[case("spaceship1", "3", "4 points left")]
+#[case("spaceship1", "3a", "Unexpected move: a")]
+#[case("3d1", ". 2 .\nA * S\n", "A = 5, B = 0: Expected 120 but got 10")]
+#[case("3d1", "A + B\n", "No S in the grid")]
+fn rejects_wrong_answers(#[case] task: &str, #[case] answer: &str, #[case] expected: &str) {
+    let entry = load(task, answer);
+    assert!(!entry.verified);
+    assert_eq!(entry.error.as_deref(), Some(expected));
+}
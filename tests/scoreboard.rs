@@ -0,0 +1,98 @@
+use icfpc2024::scoreboard::{self, Change, FamilySnapshot, Snapshot, Table, TaskScore};
+
+const BOARD: &str = "\
+| # | team | lambdaman1 | lambdaman2 |
+|---|------|-----------|:---:|
+| 1 | fox | 33 | 120 |
+| 2 | gon | 35 | |
+";
+
+fn score(task: &str, ours: Option<u64>, best: Option<u64>) -> TaskScore {
+    TaskScore {
+        task: task.to_owned(),
+        ours,
+        best,
+    }
+}
+
+fn family(scores: Vec<TaskScore>) -> FamilySnapshot {
+    FamilySnapshot {
+        family: "lambdaman".to_owned(),
+        scoreboard: Table::parse(BOARD),
+        scores,
+    }
+}
+
+#[test]
+fn parses_table() {
+    let table = Table::parse(BOARD);
+    assert_eq!(table.header, ["#", "team", "lambdaman1", "lambdaman2"]);
+    assert_eq!(table.rows.len(), 2);
+    assert_eq!(table.row("gon").unwrap(), ["2", "gon", "35", ""]);
+    assert_eq!(table.rank("fox"), Some(1));
+    assert_eq!(table.rank("nobody"), None);
+}
+
+#[test]
+fn parses_task_scores() {
+    let page = "Welcome!\n* [lambdaman1] Your score: 33. Best score: 30.\n* [lambdaman2] Best score: 100.\n* [lambdaman3]\n";
+    assert_eq!(
+        scoreboard::task_scores(page),
+        [
+            score("lambdaman1", Some(33), Some(30)),
+            score("lambdaman2", None, Some(100)),
+            score("lambdaman3", None, None),
+        ]
+    );
+}
+
+#[test]
+fn finds_changes() {
+    let previous = family(vec![
+        score("lambdaman1", Some(40), None),
+        score("lambdaman2", Some(100), None),
+        score("lambdaman3", Some(50), None),
+    ]);
+    let current = family(vec![
+        score("lambdaman1", Some(33), None),
+        score("lambdaman2", Some(120), None),
+        score("lambdaman3", Some(50), None),
+        score("lambdaman4", Some(7), None),
+        score("lambdaman5", None, None),
+    ]);
+    assert_eq!(
+        scoreboard::changes(Some(&previous), &current),
+        [
+            Change::Improved {
+                task: "lambdaman1".to_owned(),
+                from: 40,
+                to: 33
+            },
+            Change::Worsened {
+                task: "lambdaman2".to_owned(),
+                from: 100,
+                to: 120
+            },
+            Change::Solved {
+                task: "lambdaman4".to_owned(),
+                score: 7
+            },
+        ]
+    );
+}
+
+#[test]
+fn snapshot_round_trip() {
+    let snapshot = Snapshot {
+        taken_at: "2024-06-29T12:00:00.000Z".to_owned(),
+        families: vec![family(vec![score("lambdaman1", Some(33), None)])],
+    };
+    let path =
+        std::env::temp_dir().join(format!("icfpc2024-snapshots-{}.jsonl", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    scoreboard::append_snapshot(&path, &snapshot).unwrap();
+    scoreboard::append_snapshot(&path, &snapshot).unwrap();
+    let loaded = scoreboard::load_snapshots(&path).unwrap();
+    assert_eq!(loaded, [snapshot.clone(), snapshot]);
+    std::fs::remove_file(&path).unwrap();
+}
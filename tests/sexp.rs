@@ -0,0 +1,50 @@
+use icfpc2024::{
+    ast::{sexp, Expr, Value},
+    token,
+};
+use num_bigint::BigInt;
+use rstest::rstest;
+
+#[rstest]
+#[case::unary("U- I$")]
+#[case::itos("U$ I4%34")]
+#[case::sub("B- I$ I#")]
+#[case::concat("B. S4% S34")]
+#[case::take("BT I$ S4%34")]
+#[case::if_ex("? B> I# I$ S9%3 S./")]
+#[case::boolean("B| T F")]
+#[case::lambda_ex("B$ B$ L# L$ v# B. SB%,,/ S}Q/2,$_ IK")]
+#[case::limits_ex(r#"B$ B$ L" B$ L# B$ v" B$ v# v# L# B$ v" B$ v# v# L" L# ? B= v# I! I" B$ L$ B+ B$ v" v$ B$ v" v$ B- v# I" I%"#)]
+#[case::escape(r#"B. S3!9} S`()`v~"#)]
+fn display_round_trip(#[case] program: &str) -> anyhow::Result<()> {
+    let expr: Expr = program.parse()?;
+    let read = sexp::parse(&expr.to_string())?;
+    assert_eq!(read, expr);
+    assert_eq!(token::encode(&read.to_tokens())?, program);
+    Ok(())
+}
+
+#[rstest]
+#[case::apply_keyword(r#"(Apply (λ v1 . (+ v1 v1)) 21)"#, BigInt::from(42).into())]
+#[case::curried(r#"((λ v1 . (λ v2 . (- v1 v2))) 5 3)"#, BigInt::from(2).into())]
+#[case::negative("(- 3)", BigInt::from(-3).into())]
+#[case::named_ops(
+    r#"(ToString (ToInt (. (Take 2 "b\nc") (Drop 1 "x\"\\"))))"#,
+    "b\n\"\\".to_owned().into()
+)]
+#[case::if_ex(r#"(if (! (< 1 2)) "yes" "no")"#, "no".to_owned().into())]
+fn eval(#[case] s: &str, #[case] expected: Value) -> anyhow::Result<()> {
+    assert_eq!(sexp::parse(s)?.eval()?, expected);
+    Ok(())
+}
+
+#[rstest]
+#[case::unterminated_list("(+ 1 2", "end of input")]
+#[case::unterminated_string(r#"(. "ab"#, "Unterminated string")]
+#[case::arity("(+ 1)", "Wrong number of arguments for + at 0")]
+#[case::unknown_atom("(+ 1 x)", "Unknown atom at 5: x")]
+#[case::trailing("1 2", "Unexpected input after expression at 2")]
+fn errors(#[case] s: &str, #[case] message: &str) {
+    let err = sexp::parse(s).unwrap_err().to_string();
+    assert!(err.contains(message), "{err}");
+}
@@ -0,0 +1,87 @@
+use icfpc2024::{
+    assemble::assemble,
+    ast::{stdlib, Expr, Value},
+    token::BinaryOp,
+};
+use num_bigint::BigInt;
+use rstest::rstest;
+
+fn call(f: Expr, args: Vec<Expr>) -> Expr {
+    args.into_iter().fold(f, |f, x| {
+        Expr::BinaryOp(BinaryOp::Apply, f.into(), x.into())
+    })
+}
+
+fn int(i: i64) -> Expr {
+    BigInt::from(i).into()
+}
+
+fn string(s: &str) -> Expr {
+    s.to_owned().into()
+}
+
+/// アセンブルして `Expr` にする
+fn asm(src: &str) -> anyhow::Result<Expr> {
+    assemble(src)?.join(" ").parse()
+}
+
+fn factorial() -> anyhow::Result<Expr> {
+    asm("Lf Ln ? B= vn 0 1 B* vn B$ vf B- vn 1")
+}
+
+#[rstest]
+#[case::repeat(
+    call(stdlib::repeat(), vec![string("ab"), int(3)]),
+    "B$ B$ repeat \"ab\" 3",
+    "ababab".to_owned().into()
+)]
+#[case::repeat_zero(
+    call(stdlib::repeat(), vec![string("ab"), int(0)]),
+    "B$ B$ repeat \"ab\" 0",
+    String::new().into()
+)]
+#[case::digit_sum(
+    call(stdlib::fold_digits(), vec![int(10), asm("La Ld B+ va vd").unwrap(), int(0), int(12345)]),
+    "B$ B$ B$ B$ fold_digits 10 La Ld B+ va vd 0 12345",
+    BigInt::from(15).into()
+)]
+#[case::reverse_base4(
+    // 27 = 123 (4 進数) -> 321 (4 進数) = 57
+    call(stdlib::fold_digits(), vec![int(4), asm("La Ld B+ B* va 4 vd").unwrap(), int(0), int(27)]),
+    "B$ B$ B$ B$ fold_digits 4 La Ld B+ B* va 4 vd 0 27",
+    BigInt::from(57).into()
+)]
+#[case::fold_zero(
+    call(stdlib::fold_digits(), vec![int(10), asm("La Ld B+ va vd").unwrap(), int(7), int(0)]),
+    "B$ B$ B$ B$ fold_digits 10 La Ld B+ va vd 7 0",
+    BigInt::from(7).into()
+)]
+#[case::reverse(
+    call(stdlib::reverse(), vec![string("hello")]),
+    "B$ reverse \"hello\"",
+    "olleh".to_owned().into()
+)]
+#[case::reverse_empty(
+    call(stdlib::reverse(), vec![string("")]),
+    "B$ reverse \"\"",
+    String::new().into()
+)]
+#[case::char_at(
+    call(stdlib::char_at(), vec![string("hello"), int(1)]),
+    "B$ B$ char_at \"hello\" 1",
+    "e".to_owned().into()
+)]
+#[case::y(
+    call(stdlib::y(), vec![factorial().unwrap(), int(5)]),
+    "B$ B$ Y Lf Ln ? B= vn 0 1 B* vn B$ vf B- vn 1 5",
+    BigInt::from(120).into()
+)]
+fn combinator(
+    #[case] expr: Expr,
+    #[case] src: &str,
+    #[case] expected: Value,
+) -> anyhow::Result<()> {
+    assert_eq!(expr.eval()?, expected);
+    assert_eq!(asm(src)?.eval()?, expected);
+    Ok(())
+}
@@ -0,0 +1,51 @@
+use std::fs;
+
+use icfpc2024::tasks::{self, Manifest, ManifestEntry};
+use rstest::rstest;
+
+#[rstest]
+#[case("lambdaman12", Some(("lambdaman", 12)))]
+#[case("3d1", Some(("3d", 1)))]
+#[case("lambdaman", None)]
+#[case("12", None)]
+fn split_task(#[case] task: &str, #[case] expected: Option<(&str, u32)>) {
+    assert_eq!(tasks::split_task(task), expected);
+}
+
+#[test]
+fn reads_pages() {
+    let index = "You're now looking at the [index]. Try our [echo] service or look at the [scoreboard].\n\nThe following courses are available:\n* [lambdaman]\n* [spaceship]\n* [3d]\n* [efficiency]\nSee [lambdaman].";
+    assert_eq!(
+        tasks::families(index),
+        ["lambdaman", "spaceship", "3d", "efficiency"]
+    );
+
+    let page = "* [3d10] Your score: 5.\n* [3d2]\n* [3d1]\n* [lambdaman1]\n* [3d 4]\n";
+    assert_eq!(tasks::tasks("3d", page), ["3d1", "3d2", "3d10"]);
+}
+
+#[test]
+fn manifest_round_trip() {
+    let dir = std::env::temp_dir().join(format!("icfpc2024-manifest-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let entry = |family: &str, size| ManifestEntry {
+        family: family.to_owned(),
+        fetched_at: "2024-06-29T12:00:00.000Z".to_owned(),
+        size,
+    };
+
+    let mut manifest = Manifest::load(&dir).unwrap();
+    assert_eq!(manifest.get("lambdaman1"), None);
+    manifest.insert("lambdaman10", entry("lambdaman", 30));
+    manifest.insert("lambdaman2", entry("lambdaman", 20));
+    manifest.save().unwrap();
+
+    let text = fs::read_to_string(dir.join("manifest.json")).unwrap();
+    assert!(
+        text.find("\"lambdaman2\"").unwrap() < text.find("\"lambdaman10\"").unwrap(),
+        "{text}"
+    );
+    let manifest = Manifest::load(&dir).unwrap();
+    assert_eq!(manifest.get("lambdaman10"), Some(&entry("lambdaman", 30)));
+    fs::remove_dir_all(&dir).unwrap();
+}
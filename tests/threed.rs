@@ -0,0 +1,169 @@
+use std::fs;
+
+use icfpc2024::threed::{self, Cell, Example, Grid, Simulator};
+use num_bigint::BigInt;
+use rstest::rstest;
+
+#[test]
+fn parses_data_outputs() {
+    for entry in fs::read_dir("data/3d").unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_some_and(|ext| ext == "out") {
+            let grid = fs::read_to_string(&path)
+                .unwrap()
+                .parse::<Grid>()
+                .unwrap_or_else(|err| panic!("{}: {err:#}", path.display()));
+            grid.check()
+                .unwrap_or_else(|err| panic!("{}: {err:#}", path.display()));
+        }
+    }
+}
+
+#[test]
+fn display_round_trip() {
+    let src = "solve 3d1\n . . 1\n A + S\n-4 @ 1\n\n";
+    let grid = src.parse::<Grid>().unwrap();
+    assert_eq!((grid.width(), grid.height()), (3, 3));
+    assert_eq!(grid.get(1, 1), Some(&Cell::Add));
+    assert_eq!(grid.get(0, 2), Some(&Cell::Integer((-4).into())));
+    assert_eq!(grid.to_string(), ". . 1\nA + S\n-4 @ 1\n");
+    assert_eq!(grid.to_string().parse::<Grid>().unwrap(), grid);
+}
+
+#[rstest]
+#[case(". x\n", "At (1, 0): Unknown cell: x")]
+#[case(". 100\n", "At (1, 0): Integer out of range -99..=99: 100")]
+#[case("A + B\n", "No S in the grid")]
+fn errors(#[case] src: &str, #[case] expected: &str) {
+    let err = src
+        .parse::<Grid>()
+        .and_then(|grid| grid.check())
+        .unwrap_err();
+    assert_eq!(format!("{err:#}"), expected);
+}
+
+#[rstest]
+#[case(". 2 .\n-7 / S\n", 0, 0, -3)]
+#[case(". 2 .\n-7 % S\n", 0, 0, -1)]
+#[case(". 2 .\n-7 - S\n", 0, 0, -9)]
+#[case(". B .\nA * S\n", 6, 7, 42)]
+#[case(". 3 .\n3 = S\n", 0, 0, 3)]
+#[case(". 3 .\n4 # S\n", 0, 0, 3)]
+#[case("A > S\n", -5, 0, -5)]
+#[case(". S\n. ^\n. A\n", 8, 0, 8)]
+fn runs(#[case] src: &str, #[case] a: i64, #[case] b: i64, #[case] expected: i64) {
+    let grid = src.parse::<Grid>().unwrap();
+    let outcome = threed::run(&grid, &a.into(), &b.into()).unwrap();
+    assert_eq!(outcome.answer, BigInt::from(expected));
+}
+
+#[test]
+fn time_warp() {
+    // tick 1 で 1 を `@` の下に運び、tick 2 で `S` に 5 を書いて tick 1 に戻る
+    let src = ". . 5 .\nS 2 @ 0\n. . . .\n. . ^ .\n. . 1 .\n";
+    let grid = src.parse::<Grid>().unwrap();
+    let mut simulator = Simulator::new(&grid, &0.into(), &0.into());
+    let tick = simulator.step().unwrap();
+    assert_eq!((tick.from, tick.to, tick.submitted), (1, 2, None));
+    let tick = simulator.step().unwrap();
+    assert_eq!((tick.from, tick.to), (2, 1));
+    assert_eq!(tick.writes, vec![((0, 1), Cell::Integer(5.into()))]);
+    assert_eq!(tick.submitted, Some(5.into()));
+}
+
+#[test]
+fn trace_json() {
+    let src = ". . 5 .\nS 2 @ 0\n. . . .\n. . ^ .\n. . 1 .\n";
+    let grid = src.parse::<Grid>().unwrap();
+    let mut simulator = Simulator::new(&grid, &0.into(), &0.into());
+    let board = serde_json::to_value(simulator.board()).unwrap();
+    assert_eq!(board["x"], 0);
+    let rows = board["rows"].as_array().unwrap();
+    assert_eq!(rows.len(), 5);
+    assert_eq!(rows[1][0], "S");
+
+    let tick = simulator.step().unwrap();
+    assert!(!tick.is_warp());
+    let tick = simulator.step().unwrap();
+    assert!(tick.is_warp());
+    let json = serde_json::to_value(&tick).unwrap();
+    assert_eq!(json["to"], 1);
+    assert_eq!(json["warp"], true);
+    assert_eq!(json["submitted"], "5");
+    assert_eq!(json["writes"][0]["cell"], "5");
+}
+
+#[test]
+fn factorial() {
+    let grid = fs::read_to_string("data/3d/3d1.manual.out")
+        .unwrap()
+        .parse::<Grid>()
+        .unwrap();
+    let outcome = threed::run(&grid, &5.into(), &0.into()).unwrap();
+    assert_eq!(outcome.answer, BigInt::from(120));
+    assert_eq!((outcome.width, outcome.height, outcome.ticks), (8, 8, 4));
+    assert_eq!(outcome.volume, 256);
+}
+
+#[rstest]
+#[case("1 > . < 2\n. . S . .\n", "Conflicting writes at (2, 0): 1 and 2")]
+#[case("3 > . < 3\n. . S . .\n", "Conflicting writes at (2, 0): 3 and 3")]
+#[case(". 0 .\n1 / S\n", "Division by zero at (1, 1) on tick 1")]
+#[case(". 1 .\n1 @ 0\n. 1 .\n", "Time warp to tick 0 on tick 1")]
+#[case(". 1 .\n1 # S\n", "Stuck on tick 1")]
+#[case(
+    ". . 7 . .\n. -2 @ 0 .\n. . . . .\n. . ^ . .\n. . 1 . .\n",
+    "Exceeded 100 ticks"
+)]
+fn run_errors(#[case] src: &str, #[case] expected: &str) {
+    let grid = src.parse::<Grid>().unwrap();
+    let err = Simulator::new(&grid, &0.into(), &0.into())
+        .with_max_steps(100)
+        .run()
+        .unwrap_err();
+    assert_eq!(format!("{err:#}"), expected);
+}
+
+#[test]
+fn examples() {
+    let statement = fs::read_to_string("data/3d/3d4.in").unwrap();
+    let example = |a: i64, b: i64, answer: i64| Example {
+        a: a.into(),
+        b: b.into(),
+        answer: answer.into(),
+    };
+    assert_eq!(
+        threed::examples(&statement).unwrap(),
+        vec![example(3, 7, 7), example(-2, -6, -2)]
+    );
+    let statement = fs::read_to_string("data/3d/3d1.in").unwrap();
+    assert_eq!(
+        threed::examples(&statement).unwrap(),
+        vec![example(5, 0, 120)]
+    );
+    assert!(threed::examples("# Input\n").is_err());
+}
+
+#[rstest]
+#[case("data/3d/3d1.manual.out", "data/3d/3d1.in", 256)]
+#[case("data/3d/3d4.kaku.manual.out", "data/3d/3d4.in", 264)]
+fn validate(#[case] program: &str, #[case] statement: &str, #[case] volume: u64) {
+    let grid = fs::read_to_string(program)
+        .unwrap()
+        .parse::<Grid>()
+        .unwrap();
+    let examples = threed::examples(&fs::read_to_string(statement).unwrap()).unwrap();
+    let score = threed::validate(&grid, &examples, threed::MAX_STEPS).unwrap();
+    assert_eq!(score.volume, volume);
+}
+
+#[test]
+fn validate_rejects_wrong_programs() {
+    let grid = ". 2 .\nA * S\n".parse::<Grid>().unwrap();
+    let examples = threed::examples(
+        "# Example\n  * `A = 3`\n    `Answer = 6`\n  * `A = 4`\n    `Answer = 9`\n",
+    )
+    .unwrap();
+    let err = threed::validate(&grid, &examples, 100).unwrap_err();
+    assert_eq!(err.to_string(), "A = 4, B = 0: Expected 9 but got 8");
+}
@@ -0,0 +1,161 @@
+use std::fs;
+
+use icfpc2024::threed::{
+    self,
+    compile::{self, parse},
+};
+use num_bigint::BigInt;
+use rstest::rstest;
+
+const FACTORIAL: &str = "
+# 階乗
+let n = A
+let acc = 1
+while n != 0 {
+    acc = acc * n
+    n = n - 1
+}
+return acc
+";
+
+/// 盤面を実行した答えがそのまま実行した値と同じ
+fn check(src: &str, inputs: &[(i64, i64)]) {
+    let program = parse(src).unwrap();
+    let grid = compile::compile_program(&program).unwrap();
+    for &(a, b) in inputs {
+        let (a, b) = (BigInt::from(a), BigInt::from(b));
+        let expected = compile::interpret(&program, &a, &b, 10_000).unwrap();
+        let outcome = threed::run(&grid, &a, &b)
+            .unwrap_or_else(|err| panic!("A = {a}, B = {b}: {err:#}\n{grid}"));
+        assert_eq!(outcome.answer, expected, "A = {a}, B = {b}\n{grid}");
+    }
+}
+
+#[rstest]
+#[case::factorial(FACTORIAL)]
+#[case::gcd(
+    "
+let a = A
+let b = B
+while b != 0 {
+    let t = a % b
+    a = b
+    b = t
+}
+return A * B / a
+"
+)]
+#[case::abs(
+    "
+let i = 0
+while (i - A) * (i + A) != 0 {
+    i = i + 1
+}
+return i
+"
+)]
+#[case::max(
+    "
+let i = 0
+while (i - (A - B)) * (i + (A - B)) != 0 {
+    i = i + 1
+}
+return (A + B + i) / 2
+"
+)]
+#[case::digit_sum(
+    "
+let n = A
+let sum = 0
+let unused = 0
+while n != 0 {
+    sum = sum + n % 10
+    unused = unused + 1
+    n = n / 10
+}
+return sum
+"
+)]
+#[case::big_literal(
+    "
+let n = A
+while n == A {
+    n = n + 12345
+}
+return -n * 1000 + 7
+"
+)]
+#[case::straight("return (A + 3) * (B - A) % 7 - -2")]
+#[case::constant("return 200 * 3")]
+fn compiles(#[case] src: &str) {
+    check(
+        src,
+        &[(1, 1), (2, 3), (5, 5), (7, 1), (6, 4), (12, 18), (99, 1)],
+    );
+}
+
+#[test]
+fn factorial_solves_3d1() {
+    let grid = compile::compile(FACTORIAL).unwrap();
+    let statement = fs::read_to_string("data/3d/3d1.in").unwrap();
+    let examples = threed::examples(&statement).unwrap();
+    let score = threed::validate(&grid, &examples, threed::MAX_STEPS).unwrap();
+    // 今の配置は 15x29 で 8 tick（手で書いたものは 256）
+    assert!(grid.width() <= 15 && grid.height() <= 29, "{grid}");
+    assert!(score.volume <= 3480, "volume {}", score.volume);
+}
+
+/// 乱数で作った式を比べる（配線がぶつからないか）
+#[test]
+fn random_expressions() {
+    let mut seed = 12345u64;
+    let mut next = |n: u64| {
+        seed = seed
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        (seed >> 33) % n
+    };
+    fn expr(depth: u32, next: &mut impl FnMut(u64) -> u64, vars: &[&str]) -> String {
+        if depth == 0 || next(4) == 0 {
+            return match next(4) {
+                0 => (next(20) + 1).to_string(),
+                1 => "A".to_owned(),
+                2 => "B".to_owned(),
+                _ => vars[next(vars.len() as u64) as usize].to_owned(),
+            };
+        }
+        let op = ["+", "-", "*", "+"][next(4) as usize];
+        let l = expr(depth - 1, next, vars);
+        let r = expr(depth - 1, next, vars);
+        format!("({l} {op} {r})")
+    }
+    for _ in 0..30 {
+        let update = expr(3, &mut next, &["x", "i"]);
+        let result = expr(3, &mut next, &["x", "i"]);
+        let src = format!(
+            "let x = A\nlet i = 3\nwhile i != 0 {{\n  x = {update} % 1000\n  i = i - 1\n}}\nreturn {result}\n"
+        );
+        check(&src, &[(1, 2), (-3, 4), (5, -6)]);
+    }
+}
+
+#[rstest]
+#[case("return", "Unexpected end of input at 2:1")]
+#[case("let = 1\nreturn 0", "Expected identifier at 1:5")]
+#[case("while A > 0 { }\nreturn 0", "Unexpected character at 1:9: >")]
+#[case("while A { }\nreturn 0", "Expected '==' or '!=' at 1:9")]
+#[case("return A B", "Unexpected input at 1:10")]
+#[case("return x", "Unknown variable x at 1:8")]
+#[case("while A != 0 { x = 1 }\nreturn 0", "Unknown variable x at 1:16")]
+#[case(
+    "let x = A * 2\nwhile x != 0 { x = x - 1 }\nreturn 0",
+    "Initial value of x must be"
+)]
+#[case("return 1 / 0", "Division by zero")]
+fn errors(#[case] src: &str, #[case] expected: &str) {
+    let err = compile::compile(src).unwrap_err();
+    assert!(
+        format!("{err:#}").contains(expected),
+        "{err:#} does not contain {expected}"
+    );
+}
@@ -0,0 +1,83 @@
+use std::fs;
+
+use icfpc2024::threed::{
+    self, compile,
+    optimize::{self, Move},
+    Example, Grid,
+};
+use num_bigint::BigInt;
+use rstest::rstest;
+
+const FACTORIAL: &str = "
+let n = A
+let acc = 1
+while n != 0 {
+    acc = acc * n
+    n = n - 1
+}
+return acc
+";
+
+#[test]
+fn removes_dead_cells() {
+    let grid = ". . . . 7\nA > S . .\n. . . . .\n. 3 . . .\n"
+        .parse::<Grid>()
+        .unwrap();
+    let examples = [3, -4].map(|a| Example {
+        a: a.into(),
+        b: 0.into(),
+        answer: a.into(),
+    });
+    let optimized = optimize::optimize(&grid, &examples, 1000).unwrap();
+    assert_eq!(optimized.grid.to_string(), "A > S\n");
+    assert!(!optimized.moves.is_empty());
+    assert_eq!(optimized.score.volume, 3);
+}
+
+#[rstest]
+#[case(Move::DeleteRow(1), ". 1\n. 3\n. 4\n", ". 1\n. 4\n")]
+#[case(Move::DeleteColumn(0), ". 1 .\n. . 3\n. 4 .\n", "1 .\n. 3\n4 .\n")]
+#[case(Move::SwapRows(0), ". 1 .\n. 2 .\n4 . .\n", ". 2 .\n. 1 .\n4 . .\n")]
+#[case(Move::SwapColumns(1), ". 1 .\n. 2 .\n4 . .\n", ". . 1\n. . 2\n4 . .\n")]
+#[case(Move::Remove((1, 0)), ". 1 .\n. 2 .\n4 . .\n", ". . .\n. 2 .\n4 . .\n")]
+#[case(Move::Shift((1, 0), (2, 0)), ". 1 .\n. 2 .\n4 . .\n", ". . 1\n. 2 .\n4 . .\n")]
+#[case(Move::Shift((1, 0), (1, 1)), ". 1 .\n. 2 .\n4 . .\n", "")]
+fn apply(#[case] m: Move, #[case] src: &str, #[case] expected: &str) {
+    let grid = src.parse::<Grid>().unwrap();
+    let applied = m.apply(&grid).map(|grid| grid.to_string());
+    assert_eq!(applied.as_deref().unwrap_or(""), expected);
+}
+
+#[test]
+fn keeps_warp_targets() {
+    // 間の空き行を消しても `@` は同じマスに書く
+    let grid = compile::compile(FACTORIAL).unwrap();
+    let empty_row = grid
+        .rows
+        .iter()
+        .position(|row| row.iter().all(|cell| *cell == threed::Cell::Empty))
+        .unwrap();
+    let deleted = Move::DeleteRow(empty_row).apply(&grid).unwrap();
+    assert_eq!(deleted.height(), grid.height() - 1);
+    for (a, answer) in [(1, 1), (4, 24), (6, 720)] {
+        let outcome = threed::run(&deleted, &a.into(), &0.into()).unwrap();
+        assert_eq!(outcome.answer, BigInt::from(answer));
+    }
+}
+
+#[test]
+fn shrinks_compiled_factorial() {
+    let grid = compile::compile(FACTORIAL).unwrap();
+    let statement = fs::read_to_string("data/3d/3d1.in").unwrap();
+    let examples = threed::examples(&statement).unwrap();
+    let before = threed::validate(&grid, &examples, threed::MAX_STEPS).unwrap();
+    let optimized = optimize::optimize(&grid, &examples, 2000).unwrap();
+    assert!(optimized.score.volume < before.volume);
+    let after = threed::validate(&optimized.grid, &examples, threed::MAX_STEPS).unwrap();
+    assert_eq!(after, optimized.score);
+    // 例にない入力でも止まって同じ答えを出す
+    for (a, answer) in [(0, 1), (1, 1), (6, 720)] {
+        let outcome = threed::run(&optimized.grid, &a.into(), &0.into()).unwrap();
+        assert_eq!(outcome.answer, BigInt::from(answer), "A = {a}");
+    }
+}